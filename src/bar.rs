@@ -1,168 +1,872 @@
-use crate::modules::{BatteryModule, NetworkModule};
-use crate::niri::NiriIpc;
-use crate::config::Config;
-use chrono::Local;
+use crate::canvas::{Canvas, Direction, Paint, Rect, Shape};
+use crate::modules::{
+    BacklightModule, BatteryModule, CpuModule, DiskModule, KeyboardLayoutModule, MemoryModule, ModuleOutput,
+    ModuleState, NetworkModule, TaskbarModule, TemperatureModule, WindowModule,
+};
+use crate::compositor::{Action, Compositor};
+use crate::config::{Config, DiskDisplay, ModuleLayout, Overflow, SeparatorKind, StateStyle};
+use crate::inotify;
+use crate::layout::{self, Item, Region};
+use crate::sources::{Clock, Sources};
+use chrono::{DateTime, Local, Timelike};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Advance of the built-in bitmap font
+const CHAR_WIDTH: i32 = 6;
+
+/// Height of the built-in bitmap font, and so the least a bar can be
+pub const TEXT_HEIGHT: u32 = 8;
+
+// Blank cells between the end of a marquee and its next repetition
+const MARQUEE_GAP: i32 = 3;
+
+// Redraw rate while a marquee is scrolling
+const MARQUEE_FRAME: Duration = Duration::from_millis(33);
+
+// How often modules backed by polled files, e.g. battery or cpu, are re-read
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+fn text_width(text: &str) -> i32 {
+    text.graphemes(true).count() as i32 * CHAR_WIDTH
+}
+
+// How many graphemes are left before the ellipsis in `max_width`
+fn ellipsis_cut(max_width: i32) -> usize {
+    ((max_width - CHAR_WIDTH) / CHAR_WIDTH).max(0) as usize
+}
+
+// Cut `text` at a grapheme boundary so that it plus an ellipsis fits `max_width`
+fn ellipsize(text: &str, max_width: i32) -> String {
+    let mut out: String = text.graphemes(true).take(ellipsis_cut(max_width)).collect();
+    out.push('…');
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+}
+
+// What clicking part of a module's text does
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    graphemes: Range<usize>,
+    left: Option<Action>,
+    middle: Option<Action>,
+    right: Option<Action>,
+}
+
+// What a module shows, from one read of its source
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Content {
+    text: String,
+    state: ModuleState,
+    targets: Vec<Target>,
+    // Shown in place of the text while the pointer is over the module
+    tooltip: Option<String>,
+}
+
+// A module measured but not yet placed
+struct Measured {
+    content: Content,
+    item: Item,
+}
+
+// A module placed on the bar
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    name: String,
+    text: String,
+    state: ModuleState,
+    region: Region,
+    rect: Rect,
+    content: Rect,
+    targets: Vec<Target>,
+}
 
 pub struct BarRenderer {
     battery: BatteryModule,
     network: NetworkModule,
-    niri: Option<NiriIpc>,
+    cpu: CpuModule,
+    memory: MemoryModule,
+    disk: DiskModule,
+    temperature: TemperatureModule,
+    backlight: BacklightModule,
+    window: WindowModule,
+    taskbar: TaskbarModule,
+    keyboard_layout: KeyboardLayoutModule,
+    compositor: Option<Box<dyn Compositor>>,
+    clock: Box<dyn Clock>,
     config: Config,
+    // Reference point for marquee scrolling
+    started: DateTime<Local>,
+    // Injected module content that replaces live data
+    overrides: HashMap<String, ModuleOutput>,
+    // Module under the pointer, which shows its tooltip if it has one
+    hovered: Option<String>,
+    // Module content as of the last refresh; redraws in between, e.g.
+    // marquee frames, reuse it rather than reading any source again
+    contents: HashMap<String, Content>,
+    // When a time-driven module is next due to change; None once an event
+    // has made the contents stale
+    next_refresh: Option<DateTime<Local>>,
+    
+    // Retained frame, only repainted where modules changed
+    frame: Vec<u8>,
+    frame_size: (u32, u32),
+    previous: Vec<Segment>,
 }
 
 impl BarRenderer {
     pub fn new(config: Config) -> Self {
+        Self::with_sources(config, Sources::system())
+    }
+    
+    pub fn with_sources(config: Config, sources: Sources) -> Self {
+        let started = sources.clock.now();
         Self {
-            battery: BatteryModule::new(),
-            network: NetworkModule::new(),
-            niri: NiriIpc::new(),
+            battery: BatteryModule::new(&sources.sysfs),
+            network: NetworkModule::new(
+                &config.module_config.network,
+                &sources.sysfs,
+                &sources.procfs,
+                sources.interfaces,
+            ),
+            cpu: CpuModule::new(&config.module_config.cpu, &sources.procfs, &sources.sysfs),
+            memory: MemoryModule::new(&config.module_config.memory, &sources.procfs, &sources.sysfs),
+            disk: DiskModule::new(&config.module_config.disk, sources.filesystems),
+            temperature: TemperatureModule::new(&config.module_config.temperature, &sources.sysfs),
+            backlight: BacklightModule::new(&config.module_config.backlight, &sources.sysfs),
+            window: WindowModule::new(&config.module_config.window),
+            taskbar: TaskbarModule::new(&config.module_config.taskbar),
+            keyboard_layout: KeyboardLayoutModule::new(&config.module_config.keyboard_layout),
+            compositor: sources.compositor,
+            clock: sources.clock,
             config,
+            started,
+            overrides: HashMap::new(),
+            hovered: None,
+            contents: HashMap::new(),
+            next_refresh: None,
+            frame: Vec::new(),
+            frame_size: (0, 0),
+            previous: Vec::new(),
         }
     }
 
-    pub fn render(&mut self, canvas: &mut [u8], width: u32, height: u32) {
-        // Clear with configured background color
-        let bg_color = self.config.parse_color(&self.config.style.background);
+    /// Show fixed content for `module_name` instead of live data
+    pub fn set_override(&mut self, module_name: &str, output: ModuleOutput) {
+        self.overrides.insert(module_name.to_string(), output);
+        self.invalidate();
+    }
+    
+    /// Re-read every module on the next render, e.g. because the compositor
+    /// reported a change
+    pub fn invalidate(&mut self) {
+        self.next_refresh = None;
+    }
+    
+    // Read every configured module once and note when that goes stale
+    fn refresh(&mut self) {
+        let names: Vec<String> = self.config.modules_left.iter()
+            .chain(&self.config.modules_center)
+            .chain(&self.config.modules_right)
+            .cloned()
+            .collect();
+        self.contents = names
+            .into_iter()
+            .map(|name| {
+                let content = self.read_module(&name);
+                (name, content)
+            })
+            .collect();
+        let until = chrono::Duration::from_std(self.until_change()).unwrap_or_default();
+        self.next_refresh = Some(self.clock.now() + until);
+    }
+    
+    /// Bring the retained frame up to date and return the regions that were
+    /// repainted; empty when the bar looks exactly as it did last time
+    pub fn render(&mut self, width: u32, height: u32) -> Vec<Rect> {
+        if self.next_refresh.is_none_or(|at| self.clock.now() >= at) {
+            self.refresh();
+        }
+        let segments = self.layout(width, height);
+        let full = Rect::new(0, 0, width as i32, height as i32);
+        
+        let damage = if self.frame_size != (width, height) {
+            self.frame = vec![0; (width * height * 4) as usize];
+            self.frame_size = (width, height);
+            vec![full]
+        } else {
+            self.damage(&segments, full)
+        };
         
-        for pixel in canvas.chunks_exact_mut(4) {
-            pixel.copy_from_slice(&bg_color.to_ne_bytes());
+        let mut frame = std::mem::take(&mut self.frame);
+        let mut canvas = Canvas::new(&mut frame, width, height);
+        let background = self.config.background_paint();
+        for rect in &damage {
+            canvas.set_clip(Some(*rect));
+            canvas.clear(&background);
+            self.draw_region(&mut canvas, &segments, Region::Left);
+            self.draw_region(&mut canvas, &segments, Region::Center);
+            self.draw_region(&mut canvas, &segments, Region::Right);
         }
-
-        // Render modules by position
-        let mut left_x = self.config.style.padding as i32;
-        let mut right_x = width as i32 - self.config.style.padding as i32;
-        let y = ((height - 8) / 2) as i32; // Vertically center the text
+        self.frame = frame;
+        self.previous = segments;
         
-        let fg_color = self.config.parse_color(&self.config.style.foreground);
-        let accent_color = self.config.parse_color(&self.config.style.accent);
+        damage
+    }
+    
+    /// How long until the bar may look different without an event: a
+    /// marquee moving, or a time-driven module changing
+    pub fn next_update(&self) -> Duration {
+        let next = self.until_change();
+        if self.previous.iter().any(|s| self.is_animated(s)) {
+            return next.min(MARQUEE_FRAME);
+        }
+        next
+    }
+    
+    // How long until a time-driven module may read differently: the clock
+    // ticking over, disks rotating, or a polled sysfs value changing
+    fn until_change(&self) -> Duration {
+        let mut next = POLL_INTERVAL;
         
-        // Render left modules
-        for module_name in &self.config.modules_left {
-            let text = self.get_module_text(module_name);
-            let color = if module_name == "workspaces" { accent_color } else { fg_color };
-            
-            self.draw_module(canvas, width, height, &text, left_x, y, color);
-            left_x += (text.len() as i32 * 6) + self.config.style.module_spacing as i32;
+        let disk = &self.config.module_config.disk;
+        if self.shows("disk") && disk.display == DiskDisplay::Rotate {
+            // Wake just after the next turn
+            let interval = disk.rotate_interval.max(1) as i64;
+            let now = self.clock.now();
+            let into_turn = Duration::from_secs(now.timestamp().rem_euclid(interval) as u64)
+                + Duration::from_nanos(now.timestamp_subsec_nanos() as u64);
+            let until_turn = Duration::from_secs(interval as u64).saturating_sub(into_turn);
+            next = next.min(until_turn + Duration::from_millis(1));
         }
         
-        // Render center modules (centered on screen)
-        if !self.config.modules_center.is_empty() {
-            let center_text: Vec<String> = self.config.modules_center
+        if self.shows("clock") {
+            // Wake just after the next second or minute boundary
+            let format = &self.config.module_config.clock.format;
+            let shows_seconds = ["%S", "%T", "%X", "%r", "%c", "%s", "%+"]
                 .iter()
-                .map(|m| self.get_module_text(m))
-                .collect();
-            let total_width: i32 = center_text.iter()
-                .map(|t| t.len() as i32 * 6)
-                .sum::<i32>() + 
-                (center_text.len() as i32 - 1) * self.config.style.module_spacing as i32;
-            
-            let mut center_x = (width as i32 - total_width) / 2;
-            
-            for (i, _module_name) in self.config.modules_center.iter().enumerate() {
-                let text = &center_text[i];
-                self.draw_module(canvas, width, height, text, center_x, y, fg_color);
-                center_x += (text.len() as i32 * 6) + self.config.style.module_spacing as i32;
+                .any(|spec| format.contains(spec));
+            let now = self.clock.now();
+            let into_second = Duration::from_nanos(now.timestamp_subsec_nanos() as u64);
+            let until_second = Duration::from_secs(1).saturating_sub(into_second);
+            let until_tick = if shows_seconds {
+                until_second
+            } else {
+                until_second + Duration::from_secs(59 - now.second() as u64)
+            };
+            next = next.min(until_tick + Duration::from_millis(1));
+        }
+        
+        next
+    }
+    
+    fn shows(&self, module_name: &str) -> bool {
+        self.config.modules_left.iter()
+            .chain(&self.config.modules_center)
+            .chain(&self.config.modules_right)
+            .any(|m| m == module_name)
+    }
+    
+    /// The retained frame as premultiplied ARGB8888
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+    
+    // Areas covering every module that changed since the previous frame, along
+    // with the separators on either side of it
+    fn damage(&self, segments: &[Segment], full: Rect) -> Vec<Rect> {
+        let gap = self.module_gap(full.height as u32);
+        let area = |s: &Segment| {
+            Rect::new(s.rect.x - gap, full.y, s.rect.width + 2 * gap, full.height)
+                .intersection(&full)
+        };
+        
+        let changed = segments
+            .iter()
+            .filter(|s| self.is_animated(s) || !self.previous.contains(s))
+            .map(area);
+        let removed = self.previous
+            .iter()
+            .filter(|s| !segments.contains(s))
+            .map(area);
+        
+        // Merge overlapping areas so nothing is painted twice
+        let mut damage: Vec<Rect> = Vec::new();
+        for mut rect in changed.chain(removed).filter(|r| !r.is_empty()) {
+            while let Some(i) = damage.iter().position(|d| d.intersects(&rect)) {
+                rect = rect.union(&damage.swap_remove(i));
             }
+            damage.push(rect);
         }
+        damage
+    }
+    
+    // Modules that change every frame even when their text doesn't
+    fn is_animated(&self, segment: &Segment) -> bool {
+        let padding = 2 * self.config.style.module_padding as i32;
+        self.module_layout(&segment.name).overflow == Overflow::Marquee
+            && text_width(&segment.text) > segment.content.width - padding
+    }
+    
+    // Measure every configured module and place it on the bar; modules hidden
+    // for lack of space are left out
+    fn layout(&self, width: u32, height: u32) -> Vec<Segment> {
+        let modules: Vec<(&String, Region)> = self.config.modules_left
+            .iter()
+            .map(|m| (m, Region::Left))
+            .chain(self.config.modules_center.iter().map(|m| (m, Region::Center)))
+            .chain(self.config.modules_right.iter().map(|m| (m, Region::Right)))
+            .collect();
+        
+        // Modules with nothing to show take up no space
+        let (modules, measured): (Vec<_>, Vec<_>) = modules
+            .into_iter()
+            .map(|(name, region)| {
+                let mut content = self.contents.get(name.as_str()).cloned().unwrap_or_default();
+                if self.hovered.as_ref() == Some(name) {
+                    if let Some(tooltip) = content.tooltip.take() {
+                        content = Content { text: tooltip, state: content.state, ..Content::default() };
+                    }
+                }
+                ((name, region), self.measure(name, region, content))
+            })
+            .filter(|(_, measured)| !measured.content.text.is_empty())
+            .unzip();
+        let items: Vec<Item> = measured.iter().map(|m| m.item.clone()).collect();
         
-        // Render right modules (right-aligned)
-        let mut right_modules_text = Vec::new();
-        for module_name in self.config.modules_right.iter().rev() {
-            right_modules_text.push(self.get_module_text(module_name));
+        let params = layout::Params {
+            width: width as i32,
+            height: height as i32,
+            padding: self.config.style.padding as i32,
+            gap: self.module_gap(height),
+        };
+        
+        layout::layout(&items, &params)
+            .into_iter()
+            .zip(modules.iter().zip(measured))
+            .filter_map(|(placement, ((name, region), measured))| {
+                placement.map(|p| Segment {
+                    name: name.to_string(),
+                    text: measured.content.text,
+                    state: measured.content.state,
+                    region: *region,
+                    rect: p.rect,
+                    content: p.content,
+                    targets: measured.content.targets,
+                })
+            })
+            .collect()
+    }
+    
+    /// Handle a click at surface coordinates (`x`, `y`) on the last rendered
+    /// frame. Returns whether it triggered anything, in which case the bar is
+    /// likely to change.
+    pub fn click(&mut self, x: f64, y: f64, button: Button) -> bool {
+        let Some(segment) = self.segment_at(x, y) else {
+            return false;
+        };
+        let Some(grapheme) = self.grapheme_at(segment, x as i32) else {
+            return false;
+        };
+        
+        let action = segment
+            .targets
+            .iter()
+            .find(|t| t.graphemes.contains(&grapheme))
+            .and_then(|t| match button {
+                Button::Left => t.left.clone(),
+                Button::Middle => t.middle.clone(),
+                Button::Right => t.right.clone(),
+            });
+        let (Some(action), Some(compositor)) = (action, &self.compositor) else {
+            return false;
+        };
+        
+        if let Err(e) = compositor.action(action) {
+            eprintln!("Click on {} failed: {}", segment.name, e);
+        }
+        self.invalidate();
+        true
+    }
+    
+    // The grapheme of `segment`'s text drawn at `x`, going through the same
+    // overflow as draw_segment_text; None for padding, an ellipsis or a gap
+    fn grapheme_at(&self, segment: &Segment, x: i32) -> Option<usize> {
+        // Text starts at the left of the content box, one cell per grapheme
+        let padding = self.config.style.module_padding as i32;
+        let x = x - (segment.content.x + padding);
+        let available = segment.content.width - 2 * padding;
+        if x < 0 || x >= available {
+            return None;
+        }
+        let full_width = text_width(&segment.text);
+        if full_width <= available {
+            return Some((x / CHAR_WIDTH) as usize);
+        }
+        
+        match self.module_layout(&segment.name).overflow {
+            Overflow::Ellipsis => {
+                let grapheme = (x / CHAR_WIDTH) as usize;
+                (grapheme < ellipsis_cut(available)).then_some(grapheme)
+            }
+            Overflow::Clip => Some((x / CHAR_WIDTH) as usize),
+            Overflow::Marquee => {
+                let period = full_width + MARQUEE_GAP * CHAR_WIDTH;
+                let x = (x + self.marquee_offset(segment)) % period;
+                (x < full_width).then_some((x / CHAR_WIDTH) as usize)
+            }
+        }
+    }
+    
+    /// Handle `steps` notches of scrolling at (`x`, `y`), negative for up.
+    /// Returns whether it triggered anything.
+    pub fn scroll(&mut self, x: f64, y: f64, steps: i32) -> bool {
+        let Some(segment) = self.segment_at(x, y) else {
+            return false;
+        };
+        if steps == 0 || segment.name != "backlight" {
+            return false;
         }
         
-        for (i, module_name) in self.config.modules_right.iter().enumerate().rev() {
-            let text = &right_modules_text[self.config.modules_right.len() - 1 - i];
-            let text_width = text.len() as i32 * 6;
+        // Scrolling up brightens
+        if let Err(e) = self.backlight.adjust(-steps) {
+            eprintln!("Scroll on {} failed: {}", segment.name, e);
+        }
+        self.invalidate();
+        true
+    }
+    
+    /// Note the pointer moving to surface coordinates (`x`, `y`), or off the
+    /// bar for None. Returns whether that shows or hides a tooltip.
+    pub fn hover(&mut self, position: Option<(f64, f64)>) -> bool {
+        let hovered = position
+            .and_then(|(x, y)| self.segment_at(x, y))
+            .map(|segment| segment.name.clone());
+        if hovered == self.hovered {
+            return false;
+        }
+        let has_tooltip = |name: &Option<String>| {
+            name.as_ref()
+                .and_then(|name| self.contents.get(name))
+                .is_some_and(|content| content.tooltip.is_some())
+        };
+        let changed = has_tooltip(&self.hovered) || has_tooltip(&hovered);
+        self.hovered = hovered;
+        changed
+    }
+    
+    fn segment_at(&self, x: f64, y: f64) -> Option<&Segment> {
+        let (x, y) = (x as i32, y as i32);
+        self.previous.iter().find(|s| {
+            x >= s.rect.x && x < s.rect.right() && y >= s.rect.y && y < s.rect.bottom()
+        })
+    }
+    
+    /// Call `on_change` from a background thread whenever the backlight
+    /// changes, so the bar needn't poll it. Does nothing unless the bar
+    /// shows the backlight.
+    pub fn watch_backlight(&self, on_change: Box<dyn FnMut() -> bool + Send>) {
+        if !self.shows("backlight") {
+            return;
+        }
+        let Some(device) = self.backlight.device() else {
+            return;
+        };
+        // actual_brightness also changes when the hardware moves on its own,
+        // e.g. through firmware hotkeys
+        let files: Vec<PathBuf> = ["brightness", "actual_brightness"]
+            .iter()
+            .map(|name| device.join(name))
+            .filter(|path| path.exists())
+            .collect();
+        if let Err(e) = inotify::watch(&files, on_change) {
+            eprintln!("Failed to watch the backlight: {}", e);
+        }
+    }
+    
+    /// Whether every pixel of the bar ends up fully opaque
+    pub fn is_opaque(&self) -> bool {
+        self.config.background_paint().is_opaque()
+    }
+    
+    fn measure(&self, module_name: &str, region: Region, content: Content) -> Measured {
+        let padding = 2 * self.config.style.module_padding as i32;
+        let config = self.module_layout(module_name);
+        
+        let content_width = text_width(&content.text) + padding;
+        // Collisions may shrink a module down to one grapheme and an ellipsis,
+        // but never below an explicit min_width
+        let shrink_floor = content_width.min(2 * CHAR_WIDTH + padding);
+        let min_width = config.min_width.map_or(shrink_floor, |min| min as i32 + padding);
+        
+        let item = Item {
+            min_width,
+            max_width: config.max_width.map(|max| max as i32 + padding),
+            flex: config.flex,
+            priority: config.priority,
+            align: config.align,
+            ..Item::new(region, content_width)
+        };
+        Measured { content, item }
+    }
+    
+    fn module_layout(&self, module_name: &str) -> ModuleLayout {
+        self.config.module_layout
+            .get(module_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+    
+    // Space between neighbouring modules; shape separators take up the pill's half height
+    fn module_gap(&self, height: u32) -> i32 {
+        match self.config.style.separator.kind {
+            SeparatorKind::Arrow | SeparatorKind::Round => self.pill_height(height) / 2,
+            _ => self.config.style.module_spacing as i32,
+        }
+    }
+    
+    fn pill_height(&self, height: u32) -> i32 {
+        (height as i32 - 2 * self.config.style.module_margin as i32).max(0)
+    }
+    
+    fn module_style(&self, segment: &Segment) -> StateStyle {
+        self.config.module_style
+            .get(&segment.name)
+            .map(|s| s.for_state(segment.state))
+            .unwrap_or_default()
+    }
+    
+    fn module_background(&self, segment: &Segment) -> Option<Paint> {
+        self.module_style(segment)
+            .background
+            .or_else(|| self.config.style.module_background.clone())
+            .map(|bg| self.config.parse_paint(&bg))
+    }
+    
+    fn module_border(&self, segment: &Segment) -> u32 {
+        let color = self.module_style(segment)
+            .border
+            .or_else(|| self.config.style.module_border.clone())
+            .unwrap_or_else(|| self.config.style.foreground.clone());
+        self.config.parse_color(&color)
+    }
+    
+    // Draw the pills, text and separators for one region
+    fn draw_region(&self, canvas: &mut Canvas, segments: &[Segment], region: Region) {
+        let style = &self.config.style;
+        let pill_y = style.module_margin as i32;
+        let pill_height = self.pill_height(canvas.height());
+        let text_y = (canvas.height().saturating_sub(TEXT_HEIGHT) / 2) as i32; // Vertically center the text
+        let segments: Vec<&Segment> = segments.iter().filter(|s| s.region == region).collect();
+        
+        // Separators point away from the bar edge the region hangs off
+        let direction = match region {
+            Region::Left | Region::Center => Direction::Right,
+            Region::Right => Direction::Left,
+        };
+        
+        for segment in &segments {
+            let pill = Rect::new(segment.rect.x, pill_y, segment.rect.width, pill_height);
             
-            // Choose color based on module and state
-            let color = self.get_module_color(module_name, text);
+            if let Some(bg) = self.module_background(segment) {
+                canvas.fill_rounded_rect(pill, style.module_radius as f32, &bg);
+            }
+            if style.module_border_width > 0 {
+                let border = self.module_border(segment);
+                canvas.stroke_rounded_rect(pill, style.module_radius as f32, style.module_border_width, border);
+            }
             
-            self.draw_module(canvas, width, height, text, right_x - text_width, y, color);
-            right_x -= text_width + self.config.style.module_spacing as i32;
+            let color = self.get_module_color(&segment.name, segment.state);
+            self.draw_segment_text(canvas, segment, text_y, color);
+        }
+        
+        for pair in segments.windows(2) {
+            let (before, after) = (pair[0].rect, pair[1].rect);
+            let gap = Rect::new(before.right(), pill_y, after.x - before.right(), pill_height);
+            self.draw_separator(canvas, pair[0], pair[1], gap, text_y, direction);
         }
     }
     
-    fn get_module_text(&self, module_name: &str) -> String {
-        match module_name {
-            "workspaces" => {
-                self.niri
-                    .as_ref()
-                    .map(|n| n.get_workspace_summary())
-                    .unwrap_or_else(|| String::from("WS ?"))
+    fn draw_separator(
+        &self,
+        canvas: &mut Canvas,
+        before: &Segment,
+        after: &Segment,
+        gap: Rect,
+        text_y: i32,
+        direction: Direction,
+    ) {
+        let separator = &self.config.style.separator;
+        let color = self.config.parse_color(
+            separator.color.as_ref().unwrap_or(&self.config.style.foreground),
+        );
+        let before_bg = self.module_background(before);
+        let after_bg = self.module_background(after);
+        let before_area = Rect::new(before.rect.x, gap.y, before.rect.width, gap.height);
+        let after_area = Rect::new(after.rect.x, gap.y, after.rect.width, gap.height);
+        
+        // The shape takes the paint of the module it points away from; modules
+        // without a pill leave the bar background showing through
+        let (fill, shape) = match direction {
+            Direction::Right => (
+                after_bg.as_ref().map(|p| (p, after_area)),
+                before_bg.as_ref().map(|p| (p, before_area)),
+            ),
+            Direction::Left => (
+                before_bg.as_ref().map(|p| (p, before_area)),
+                after_bg.as_ref().map(|p| (p, after_area)),
+            ),
+        };
+        
+        match separator.kind {
+            SeparatorKind::None => {}
+            SeparatorKind::Glyph => {
+                let x = gap.x + (gap.width - text_width(&separator.glyph)) / 2;
+                self.draw_simple_text(canvas, &separator.glyph, x, text_y, color);
             }
-            "battery" => self.battery.render(),
-            "network" => self.network.render(),
-            "clock" => {
-                Local::now()
-                    .format(&self.config.module_config.clock.format)
-                    .to_string()
+            SeparatorKind::Line => {
+                let x = gap.x + (gap.width - separator.width as i32) / 2;
+                canvas.vline(x, gap.y, gap.height, separator.width, color);
+            }
+            SeparatorKind::Arrow => {
+                canvas.fill_separator(gap, Shape::Arrow, direction, shape, fill);
+            }
+            SeparatorKind::Round => {
+                canvas.fill_separator(gap, Shape::HalfCircle, direction, shape, fill);
+            }
+        }
+    }
+    
+    // Draw a module's text, truncating or scrolling it if it doesn't fit
+    fn draw_segment_text(&self, canvas: &mut Canvas, segment: &Segment, y: i32, color: u32) {
+        let padding = self.config.style.module_padding as i32;
+        let x = segment.content.x + padding;
+        let available = segment.content.width - 2 * padding;
+        let full_width = text_width(&segment.text);
+        
+        if full_width <= available {
+            self.draw_simple_text(canvas, &segment.text, x, y, color);
+            return;
+        }
+        
+        let layout = self.module_layout(&segment.name);
+        match layout.overflow {
+            Overflow::Ellipsis => {
+                self.draw_simple_text(canvas, &ellipsize(&segment.text, available), x, y, color);
+            }
+            Overflow::Clip => {
+                let clip = Rect::new(x, 0, available, canvas.height() as i32);
+                canvas.with_clip(clip, |canvas| self.draw_simple_text(canvas, &segment.text, x, y, color));
+            }
+            Overflow::Marquee => {
+                // Scroll continuously, with a second copy following the first
+                let period = full_width + MARQUEE_GAP * CHAR_WIDTH;
+                let offset = self.marquee_offset(segment);
+                
+                let clip = Rect::new(x, 0, available, canvas.height() as i32);
+                canvas.with_clip(clip, |canvas| {
+                    self.draw_simple_text(canvas, &segment.text, x - offset, y, color);
+                    self.draw_simple_text(canvas, &segment.text, x - offset + period, y, color);
+                });
             }
-            _ => String::from("?"),
         }
     }
     
-    fn get_module_color(&self, module_name: &str, text: &str) -> u32 {
+    // How far a scrolling segment's text has moved left, going by the
+    // injected clock so frames are reproducible
+    fn marquee_offset(&self, segment: &Segment) -> i32 {
+        let layout = self.module_layout(&segment.name);
+        let period = (text_width(&segment.text) + MARQUEE_GAP * CHAR_WIDTH) as i64;
+        let elapsed = (self.clock.now() - self.started).num_milliseconds().max(0);
+        (elapsed * i64::from(layout.marquee_speed) / 1000 % period) as i32
+    }
+    
+    // Read `module_name` once, deriving its state from the same reading
+    fn read_module(&self, module_name: &str) -> Content {
+        if let Some(output) = self.overrides.get(module_name) {
+            return Content {
+                text: output.text.clone(),
+                state: output.state.unwrap_or_else(|| self.state_from_text(module_name, &output.text)),
+                ..Content::default()
+            };
+        }
+        let text_and_state = |reading: Option<(String, ModuleState)>| {
+            let (text, state) = reading.unwrap_or_default();
+            Content { text, state, ..Content::default() }
+        };
+        
         match module_name {
+            "workspaces" => {
+                let Some(compositor) = &self.compositor else {
+                    return text_and_state(Some((String::from("WS ?"), ModuleState::Normal)));
+                };
+                let (text, entries) = compositor.workspace_summary();
+                let targets = entries
+                    .into_iter()
+                    .map(|entry| Target {
+                        graphemes: entry.graphemes,
+                        left: Some(Action::FocusWorkspace { id: entry.workspace_id }),
+                        middle: Some(Action::DeactivateWorkspace { id: entry.workspace_id })
+                            .filter(|action| compositor.supports(action)),
+                        right: None,
+                    })
+                    .collect();
+                Content { text, targets, ..Content::default() }
+            }
+            "taskbar" => {
+                let Some(compositor) = &self.compositor else {
+                    return Content::default();
+                };
+                let Ok(windows) = compositor.windows() else {
+                    return Content::default();
+                };
+                // Window-only backends have no workspaces to order by
+                let workspaces = compositor.workspaces().unwrap_or_default();
+                let (text, entries) = self.taskbar.render(&windows, &workspaces);
+                let targets = entries
+                    .into_iter()
+                    .map(|entry| Target {
+                        graphemes: entry.graphemes,
+                        left: Some(Action::FocusWindow { id: entry.window_id }),
+                        middle: Some(Action::CloseWindow { id: entry.window_id }),
+                        right: Some(Action::MinimizeWindow { id: entry.window_id })
+                            .filter(|action| compositor.supports(action)),
+                    })
+                    .collect();
+                Content { text, targets, ..Content::default() }
+            }
+            "keyboard_layout" => {
+                let Some(layouts) = self.compositor.as_ref().and_then(|c| c.keyboard_layouts().ok()) else {
+                    return Content::default();
+                };
+                let text = self.keyboard_layout.render(&layouts);
+                // Anywhere on the module cycles through the layouts
+                let target = Target {
+                    graphemes: 0..text.graphemes(true).count(),
+                    left: Some(Action::NextLayout),
+                    middle: None,
+                    right: Some(Action::PrevLayout),
+                };
+                Content { text, targets: vec![target], ..Content::default() }
+            }
+            "window" => {
+                let focused = self.compositor.as_ref().and_then(|c| c.focused_window().ok()).flatten();
+                text_and_state(Some((self.window.render(focused.as_ref()), ModuleState::Normal)))
+            }
             "battery" => {
-                // Parse battery percentage from text
-                if let Some(pct_str) = text.split_whitespace().find(|s| s.ends_with('%')) {
-                    if let Ok(pct) = pct_str.trim_end_matches('%').parse::<u32>() {
-                        if pct <= self.config.module_config.battery.critical_threshold {
-                            return self.config.parse_color(&self.config.style.critical);
-                        } else if pct <= self.config.module_config.battery.warning_threshold {
-                            return self.config.parse_color(&self.config.style.warning);
-                        }
-                    }
+                let text = self.battery.render();
+                let state = self.state_from_text(module_name, &text);
+                text_and_state(Some((text, state)))
+            }
+            "network" => {
+                let reading = self.network.sample(self.clock.now());
+                let reading = reading.as_ref();
+                text_and_state(Some((self.network.render(reading), self.network.state(reading))))
+            }
+            "cpu" => text_and_state(
+                self.cpu
+                    .sample(self.clock.now())
+                    .map(|reading| (self.cpu.render(&reading), self.cpu.state(&reading))),
+            ),
+            "memory" => {
+                let Some(reading) = self.memory.read() else {
+                    return Content::default();
+                };
+                Content {
+                    text: self.memory.render(&reading),
+                    state: self.memory.state(&reading),
+                    tooltip: self.memory.tooltip(&reading),
+                    ..Content::default()
                 }
-                self.config.parse_color(&self.config.style.foreground)
             }
-            "workspaces" => self.config.parse_color(&self.config.style.accent),
-            _ => self.config.parse_color(&self.config.style.foreground),
+            "disk" => {
+                let mounts = self.disk.read(self.clock.now());
+                text_and_state(Some((self.disk.render(&mounts), self.disk.state(&mounts))))
+            }
+            "temperature" => text_and_state(
+                self.temperature
+                    .read()
+                    .map(|reading| (self.temperature.render(&reading), self.temperature.state(&reading))),
+            ),
+            "backlight" => text_and_state(
+                self.backlight
+                    .read()
+                    .map(|reading| (self.backlight.render(&reading), ModuleState::Normal)),
+            ),
+            "clock" => {
+                let text = self.clock
+                    .now()
+                    .format(&self.config.module_config.clock.format)
+                    .to_string();
+                text_and_state(Some((text, ModuleState::Normal)))
+            }
+            _ => text_and_state(Some((String::from("?"), ModuleState::Normal))),
         }
     }
     
-    fn draw_module(
-        &self,
-        canvas: &mut [u8],
-        width: u32,
-        height: u32,
-        text: &str,
-        x: i32,
-        y: i32,
-        color: u32,
-    ) {
-        self.draw_simple_text(canvas, width, height, text, x, y, color);
+    // State for text that didn't come with one: only the battery's can be
+    // told from its text
+    fn state_from_text(&self, module_name: &str, text: &str) -> ModuleState {
+        if module_name != "battery" {
+            return ModuleState::Normal;
+        }
+        let pct = text.split_whitespace()
+            .find(|s| s.ends_with('%'))
+            .and_then(|s| s.trim_end_matches('%').parse::<u32>().ok());
+        let battery = &self.config.module_config.battery;
+        match pct {
+            Some(pct) if pct <= battery.critical_threshold => ModuleState::Critical,
+            Some(pct) if pct <= battery.warning_threshold => ModuleState::Warning,
+            _ => ModuleState::Normal,
+        }
     }
-
-    fn draw_simple_text(
-        &self,
-        canvas: &mut [u8],
-        width: u32,
-        height: u32,
-        text: &str,
-        x: i32,
-        y: i32,
-        color: u32,
-    ) {
-        let char_width = 6;
+    
+    fn get_module_color(&self, module_name: &str, state: ModuleState) -> u32 {
+        let module_style = self.config.module_style.get(module_name);
         
-        for (i, ch) in text.chars().enumerate() {
-            let char_x = x + (i as i32 * char_width);
-            self.draw_char(canvas, width, height, ch, char_x, y, color);
+        // A per-state foreground wins, then the global warning/critical colors
+        if let Some(color) = module_style
+            .and_then(|s| s.state_override(state))
+            .and_then(|s| s.foreground.as_ref())
+        {
+            return self.config.parse_color(color);
+        }
+        match state {
+            ModuleState::Critical => return self.config.parse_color(&self.config.style.critical),
+            ModuleState::Warning => return self.config.parse_color(&self.config.style.warning),
+            ModuleState::Normal => {}
         }
+        
+        let default = match module_name {
+            "workspaces" => &self.config.style.accent,
+            _ => &self.config.style.foreground,
+        };
+        let foreground = module_style
+            .and_then(|s| s.foreground.as_ref())
+            .unwrap_or(default);
+        self.config.parse_color(foreground)
     }
     
-    fn draw_char(
-        &self,
-        canvas: &mut [u8],
-        width: u32,
-        height: u32,
-        ch: char,
-        x: i32,
-        y: i32,
-        color: u32,
-    ) {
+    fn draw_simple_text(&self, canvas: &mut Canvas, text: &str, x: i32, y: i32, color: u32) {
+        // One cell per grapheme; the bitmap font has no combining marks
+        for (i, grapheme) in text.graphemes(true).enumerate() {
+            let char_x = x + (i as i32 * CHAR_WIDTH);
+            if let Some(ch) = grapheme.chars().next() {
+                self.draw_char(canvas, ch, char_x, y, color);
+            }
+        }
+    }
+    
+    fn draw_char(&self, canvas: &mut Canvas, ch: char, x: i32, y: i32, color: u32) {
         // Simple 5x7 bitmap patterns for common characters
         let pattern = match ch {
             '0' => [0x7C, 0xC6, 0xCE, 0xD6, 0xE6, 0xC6, 0x7C],
@@ -176,12 +880,22 @@ impl BarRenderer {
             '8' => [0x7C, 0xC6, 0xC6, 0x7C, 0xC6, 0xC6, 0x7C],
             '9' => [0x7C, 0xC6, 0xC6, 0x7E, 0x06, 0x0C, 0x78],
             ':' => [0x00, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00],
+            '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18],
+            '…' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xA8],
             '%' => [0xC6, 0xC6, 0x0C, 0x18, 0x30, 0x63, 0x63],
             '[' => [0x3C, 0x30, 0x30, 0x30, 0x30, 0x30, 0x3C],
             ']' => [0x3C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x3C],
             '|' => [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
             ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
             '?' => [0x7C, 0xC6, 0x0C, 0x18, 0x18, 0x00, 0x18],
+            // Bar graph levels, bottom-aligned
+            '▁' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7C],
+            '▂' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C],
+            '▃' => [0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x7C],
+            '▄' => [0x00, 0x00, 0x00, 0x7C, 0x7C, 0x7C, 0x7C],
+            '▅' => [0x00, 0x00, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C],
+            '▆' => [0x00, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C],
+            '▇' | '█' => [0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C],
             'A' | 'a' => [0x7C, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0xC6],
             'B' | 'b' => [0xFC, 0xC6, 0xC6, 0xFC, 0xC6, 0xC6, 0xFC],
             'C' | 'c' => [0x7C, 0xC6, 0xC0, 0xC0, 0xC0, 0xC6, 0x7C],
@@ -206,17 +920,331 @@ impl BarRenderer {
         for (row, byte) in pattern.iter().enumerate() {
             for bit in 0..8 {
                 if (byte & (1 << (7 - bit))) != 0 {
-                    let px = x + bit;
-                    let py = y + row as i32;
-                    
-                    if px >= 0 && px < width as i32 && py >= 0 && py < height as i32 {
-                        let offset = ((py * width as i32 + px) * 4) as usize;
-                        if offset + 3 < canvas.len() {
-                            canvas[offset..offset + 4].copy_from_slice(&color.to_ne_bytes());
-                        }
-                    }
+                    canvas.set_pixel(x + bit, y + row as i32, color);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::{FixedFilesystems, FixedInterfaces, ManualClock};
+    use crate::testing::Fixture;
+    use chrono::{Local, TimeZone};
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    fn renderer(sysfs: &Fixture, clock: &ManualClock, clock_format: &str) -> BarRenderer {
+        let mut config = Config::default();
+        config.module_config.clock.format = clock_format.to_string();
+        renderer_with(sysfs, clock, config)
+    }
+
+    fn renderer_with(sysfs: &Fixture, clock: &ManualClock, config: Config) -> BarRenderer {
+        let sources = Sources {
+            sysfs: sysfs.path().to_path_buf(),
+            procfs: sysfs.path().to_path_buf(),
+            clock: Box::new(clock.clone()),
+            filesystems: Box::new(FixedFilesystems::default()),
+            interfaces: Box::new(FixedInterfaces::default()),
+            compositor: None,
+        };
+        BarRenderer::with_sources(config, sources)
+    }
+
+    #[test]
+    fn clock_follows_the_injected_time() {
+        let sysfs = Fixture::new();
+        let clock = ManualClock::new(Local.with_ymd_and_hms(2024, 5, 1, 12, 34, 56).unwrap());
+        let bar = renderer(&sysfs, &clock, "%H:%M");
+
+        assert_eq!(bar.read_module("clock").text, "12:34");
+        clock.set(Local.with_ymd_and_hms(2024, 5, 1, 12, 35, 0).unwrap());
+        assert_eq!(bar.read_module("clock").text, "12:35");
+    }
+
+    #[test]
+    fn next_update_waits_for_the_clock_to_tick() {
+        let sysfs = Fixture::new();
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 12, 34, 56).unwrap()
+            + chrono::Duration::milliseconds(250);
+        let clock = ManualClock::new(now);
+
+        let seconds = renderer(&sysfs, &clock, "%H:%M:%S");
+        assert_eq!(seconds.next_update(), Duration::from_millis(751));
+
+        let minutes = renderer(&sysfs, &clock, "%H:%M");
+        assert_eq!(minutes.next_update(), Duration::from_millis(3751));
+    }
+
+    #[test]
+    fn next_update_waits_for_the_next_disk_turn() {
+        let sysfs = Fixture::new();
+        let now = Local.timestamp_opt(1003, 250_000_000).unwrap();
+        let clock = ManualClock::new(now);
+        let mut bar = renderer(&sysfs, &clock, "%H:%M");
+        bar.config.modules_right = vec!["disk".into()];
+        assert_eq!(bar.next_update(), POLL_INTERVAL);
+
+        bar.config.module_config.disk.display = DiskDisplay::Rotate;
+        bar.config.module_config.disk.rotate_interval = 4;
+        assert_eq!(bar.next_update(), Duration::from_millis(751));
+    }
+
+    #[test]
+    fn equal_min_and_max_width_fix_the_box() {
+        let sysfs = Fixture::new();
+        let clock = ManualClock::new(Local.with_ymd_and_hms(2024, 5, 1, 12, 34, 56).unwrap());
+        let widths: Vec<i32> = ["%H", "%H:%M:%S %A %B"]
+            .iter()
+            .map(|format| {
+                let mut config = Config {
+                    modules_left: vec!["clock".into()],
+                    modules_center: Vec::new(),
+                    modules_right: Vec::new(),
+                    ..Config::default()
+                };
+                config.module_config.clock.format = format.to_string();
+                let fixed = ModuleLayout {
+                    min_width: Some(60),
+                    max_width: Some(60),
+                    ..ModuleLayout::default()
+                };
+                config.module_layout.insert("clock".into(), fixed);
+                let padding = 2 * config.style.module_padding as i32;
+                let mut bar = renderer_with(&sysfs, &clock, config);
+                bar.render(800, 30);
+                bar.previous[0].rect.width - padding
+            })
+            .collect();
+        assert_eq!(widths, [60, 60]);
+    }
+
+    #[test]
+    fn battery_state_comes_from_sysfs() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/power_supply/BAT0/charge_now", "10\n")
+            .write("class/power_supply/BAT0/charge_full", "100\n");
+        let clock = ManualClock::new(Local::now());
+        let bar = renderer(&sysfs, &clock, "%H:%M");
+
+        let measured = bar.read_module("battery");
+        assert_eq!(measured.text, "LOW 10%");
+        assert_eq!(measured.state, ModuleState::Critical);
+    }
+
+    #[test]
+    fn scrolling_the_backlight_sets_it_and_is_noticed() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/backlight/intel_backlight/brightness", "50\n")
+            .write("class/backlight/intel_backlight/max_brightness", "100\n");
+        let brightness = sysfs.path().join("class/backlight/intel_backlight/brightness");
+        let mut config = Config {
+            modules_left: vec!["backlight".into()],
+            modules_center: Vec::new(),
+            modules_right: Vec::new(),
+            ..Config::default()
+        };
+        config.module_config.backlight.command =
+            Some(format!("echo {{value}} > '{}'", brightness.display()));
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer_with(&sysfs, &clock, config);
+
+        let (sender, changes) = mpsc::channel();
+        bar.watch_backlight(Box::new(move || sender.send(()).is_ok()));
+        bar.render(400, 30);
+        assert_eq!(bar.read_module("backlight").text, "BL 50%");
+        let rect = bar.previous[0].rect;
+        assert!(!bar.scroll(rect.right() as f64 + 10.0, 10.0, -1));
+
+        assert!(bar.scroll(rect.x as f64 + 1.0, 10.0, -1));
+        changes.recv_timeout(Duration::from_secs(2)).unwrap();
+        // The first event may be the truncation, before the new value
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while bar.read_module("backlight").text != "BL 55%" && Instant::now() < deadline {
+            let _ = changes.recv_timeout(Duration::from_millis(50));
+        }
+        assert_eq!(bar.read_module("backlight").text, "BL 55%");
+    }
+
+    #[test]
+    fn redraws_reuse_the_last_reading_until_it_goes_stale() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/backlight/intel_backlight/brightness", "50\n")
+            .write("class/backlight/intel_backlight/max_brightness", "100\n");
+        let config = Config {
+            modules_left: vec!["backlight".into()],
+            modules_center: Vec::new(),
+            modules_right: Vec::new(),
+            ..Config::default()
+        };
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer_with(&sysfs, &clock, config);
+        let text = |bar: &mut BarRenderer| {
+            bar.render(400, 30);
+            bar.previous[0].text.clone()
+        };
+        assert_eq!(text(&mut bar), "BL 50%");
+
+        sysfs.write("class/backlight/intel_backlight/brightness", "80\n");
+        assert_eq!(text(&mut bar), "BL 50%");
+        bar.invalidate();
+        assert_eq!(text(&mut bar), "BL 80%");
+
+        sysfs.write("class/backlight/intel_backlight/brightness", "30\n");
+        clock.set(clock.now() + chrono::Duration::from_std(POLL_INTERVAL).unwrap());
+        assert_eq!(text(&mut bar), "BL 30%");
+    }
+
+    #[test]
+    fn marquee_frames_move_the_cached_text() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/backlight/intel_backlight/brightness", "50\n")
+            .write("class/backlight/intel_backlight/max_brightness", "100\n");
+        let mut config = Config {
+            modules_left: vec!["backlight".into()],
+            modules_center: Vec::new(),
+            modules_right: Vec::new(),
+            ..Config::default()
+        };
+        config.module_layout.insert("backlight".into(), ModuleLayout {
+            max_width: Some(12),
+            overflow: Overflow::Marquee,
+            ..ModuleLayout::default()
+        });
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer_with(&sysfs, &clock, config);
+        bar.render(400, 30);
+        assert_eq!(bar.next_update(), MARQUEE_FRAME);
+
+        // Frames come faster than anything is due to change, so they don't
+        // read the backlight again
+        sysfs.write("class/backlight/intel_backlight/brightness", "80\n");
+        bar.render(400, 30);
+        assert_eq!(bar.previous[0].text, "BL 50%");
+        assert_eq!(bar.next_update(), MARQUEE_FRAME);
+    }
+
+    #[test]
+    fn marquee_redraws_match_a_full_repaint() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/backlight/intel_backlight/brightness", "50\n")
+            .write("class/backlight/intel_backlight/max_brightness", "100\n");
+        let mut config = Config {
+            modules_left: vec!["backlight".into(), "clock".into()],
+            modules_center: Vec::new(),
+            modules_right: Vec::new(),
+            ..Config::default()
+        };
+        config.style.module_background = Some("#8089b4fa".into());
+        config.style.module_radius = 6;
+        config.module_layout.insert("backlight".into(), ModuleLayout {
+            max_width: Some(12),
+            overflow: Overflow::Marquee,
+            ..ModuleLayout::default()
+        });
+        let clock = ManualClock::new(Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap());
+        let mut bar = renderer_with(&sysfs, &clock, config);
+        bar.render(400, 30);
+
+        // Only the scrolling text is damaged, so the translucent pills next
+        // to it must not be painted over a second time
+        for _ in 0..2 {
+            clock.set(clock.now() + chrono::Duration::milliseconds(100));
+            bar.render(400, 30);
+        }
+        let damaged = bar.frame.clone();
+        bar.frame_size = (0, 0);
+        bar.render(400, 30);
+        assert!(damaged == bar.frame);
+    }
+
+    #[test]
+    fn hovering_shows_the_tooltip_in_place_of_the_text() {
+        let procfs = Fixture::new();
+        procfs.write(
+            "meminfo",
+            "MemTotal: 4194304 kB\nMemFree: 1048576 kB\nMemAvailable: 2097152 kB\n\
+             Buffers: 524288 kB\nCached: 1048576 kB\n",
+        );
+        let config = Config {
+            modules_left: vec!["memory".into(), "clock".into()],
+            modules_center: Vec::new(),
+            modules_right: Vec::new(),
+            ..Config::default()
+        };
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer_with(&procfs, &clock, config);
+        bar.render(400, 30);
+        assert_eq!(bar.previous[0].text, "RAM 50%");
+
+        // The clock has no tooltip, so moving over it changes nothing
+        let (memory, other) = (bar.previous[0].rect, bar.previous[1].rect);
+        assert!(!bar.hover(Some((other.x as f64 + 1.0, 10.0))));
+        assert!(bar.hover(Some((memory.x as f64 + 1.0, 10.0))));
+        assert!(!bar.hover(Some((memory.x as f64 + 2.0, 10.0))));
+        assert!(!bar.render(400, 30).is_empty());
+        assert_eq!(bar.previous[0].text, "buf 512.0MiB cache 1.0GiB free 1.0GiB");
+
+        assert!(bar.hover(None));
+        bar.render(400, 30);
+        assert_eq!(bar.previous[0].text, "RAM 50%");
+    }
+
+    #[test]
+    fn bars_shorter_than_the_text_still_render() {
+        let sysfs = Fixture::new();
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer(&sysfs, &clock, "%H:%M");
+        for height in 0..TEXT_HEIGHT {
+            bar.render(200, height);
+        }
+    }
+
+    #[test]
+    fn cpu_state_follows_the_thresholds() {
+        let procfs = Fixture::new();
+        let clock = ManualClock::new(Local::now());
+        let bar = renderer(&procfs, &clock, "%H:%M");
+        let stat = |busy, idle| format!("cpu  {} 0 0 {} 0 0 0 0 0 0\n", busy, idle);
+
+        procfs.write("stat", &stat(10, 90));
+        let measured = bar.read_module("cpu");
+        assert_eq!((measured.text.as_str(), measured.state), ("CPU 10%", ModuleState::Normal));
+
+        procfs.write("stat", &stat(90, 110));
+        clock.set(clock.now() + chrono::Duration::seconds(5));
+        assert_eq!(bar.read_module("cpu").state, ModuleState::Warning);
+
+        procfs.write("stat", &stat(190, 110));
+        clock.set(clock.now() + chrono::Duration::seconds(5));
+        let measured = bar.read_module("cpu");
+        assert_eq!((measured.text.as_str(), measured.state), ("CPU 100%", ModuleState::Critical));
+    }
+
+    #[test]
+    fn modules_without_text_take_no_space() {
+        let sysfs = Fixture::new();
+        let clock = ManualClock::new(Local::now());
+        let mut bar = renderer(&sysfs, &clock, "%H:%M");
+        bar.config.modules_left = vec!["window".into(), "clock".into()];
+        bar.config.modules_right.clear();
+
+        let names = |bar: &mut BarRenderer| -> Vec<String> {
+            bar.refresh();
+            bar.layout(400, 30).into_iter().map(|s| s.name).collect()
+        };
+        assert_eq!(names(&mut bar), ["clock"]);
+
+        bar.config.module_config.window.empty = "desktop".into();
+        bar.window = WindowModule::new(&bar.config.module_config.window);
+        assert_eq!(names(&mut bar), ["window", "clock"]);
+    }
+}
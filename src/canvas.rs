@@ -0,0 +1,627 @@
+// Small 2D drawing layer over the ARGB8888 shm buffer
+//
+// Colors are passed in as straight (non-premultiplied) ARGB, as parsed from
+// the config. The buffer itself holds premultiplied pixels, which is what
+// wl_shm's Argb8888 format expects, and every draw call composites with
+// source-over so translucent colors and anti-aliased edges blend correctly.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right() && other.x < self.right() && self.y < other.bottom() && other.y < self.bottom()
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Rect::new(x, y, self.right().min(other.right()) - x, self.bottom().min(other.bottom()) - y)
+    }
+
+    /// Smallest rect containing both
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+
+    /// Shrink the rect by `amount` on every side
+    pub fn inset(&self, amount: i32) -> Rect {
+        Rect::new(
+            self.x + amount,
+            self.y + amount,
+            self.width - 2 * amount,
+            self.height - 2 * amount,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Arrow,
+    HalfCircle,
+}
+
+/// Which way an arrow or half-circle separator points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    /// Position along the gradient line, 0.0 - 1.0
+    pub offset: f32,
+    /// Straight-alpha ARGB
+    pub color: u32,
+}
+
+/// What a background is filled with
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Solid(u32),
+    /// CSS-style linear gradient; `angle` is in degrees, 0 pointing up and
+    /// 90 pointing right
+    LinearGradient { angle: f32, stops: Vec<ColorStop> },
+}
+
+impl Paint {
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            Paint::Solid(color) => color >> 24 == 0xFF,
+            Paint::LinearGradient { stops, .. } => stops.iter().all(|s| s.color >> 24 == 0xFF),
+        }
+    }
+
+    /// Multiply the alpha of every color by `opacity`
+    pub fn with_opacity(&self, opacity: f32) -> Paint {
+        let apply = |color: u32| {
+            let alpha = ((color >> 24) as f32 * opacity.clamp(0.0, 1.0)).round() as u32;
+            (alpha << 24) | (color & 0x00FFFFFF)
+        };
+        match self {
+            Paint::Solid(color) => Paint::Solid(apply(*color)),
+            Paint::LinearGradient { angle, stops } => Paint::LinearGradient {
+                angle: *angle,
+                stops: stops
+                    .iter()
+                    .map(|s| ColorStop { offset: s.offset, color: apply(s.color) })
+                    .collect(),
+            },
+        }
+    }
+
+    // Premultiplied color of the pixel at (x, y) for a paint spanning `area`.
+    // Gradients are interpolated in float and ordered-dithered down to 8 bits
+    // so that long, low-contrast gradients don't band.
+    fn premultiplied_at(&self, area: Rect, x: i32, y: i32) -> u32 {
+        let (angle, stops) = match self {
+            Paint::Solid(color) => return premultiply(*color, 1.0),
+            Paint::LinearGradient { angle, stops } => (*angle, stops),
+        };
+        let Some(first) = stops.first() else {
+            return 0;
+        };
+
+        // Project the pixel onto the gradient line through the area's center
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let w = area.width as f32;
+        let h = area.height as f32;
+        let length = (w * sin).abs() + (h * cos).abs();
+        let dx = x as f32 + 0.5 - (area.x as f32 + w / 2.0);
+        let dy = y as f32 + 0.5 - (area.y as f32 + h / 2.0);
+        let t = if length > 0.0 {
+            (dx * sin - dy * cos) / length + 0.5
+        } else {
+            0.0
+        };
+
+        let mut from = first;
+        let mut to = first;
+        for stop in stops {
+            to = stop;
+            if stop.offset >= t {
+                break;
+            }
+            from = stop;
+        }
+        let span = to.offset - from.offset;
+        let mix = if span > 0.0 { ((t - from.offset) / span).clamp(0.0, 1.0) } else { 0.0 };
+
+        let a = premultiplied_channels(from.color);
+        let b = premultiplied_channels(to.color);
+        let threshold = BAYER_4X4[(y & 3) as usize][(x & 3) as usize] as f32 / 16.0 - 0.5 + 1.0 / 32.0;
+        let channel = |i: usize| {
+            let value = a[i] + (b[i] - a[i]) * mix + threshold;
+            value.round().clamp(0.0, 255.0) as u32
+        };
+
+        // A premultiplied color channel can never exceed alpha
+        let alpha = channel(0);
+        let color = |i: usize| channel(i).min(alpha);
+        alpha << 24 | color(1) << 16 | color(2) << 8 | color(3)
+    }
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+pub struct Canvas<'a> {
+    data: &'a mut [u8],
+    width: u32,
+    height: u32,
+    // Drawing outside this rect is discarded
+    clip: Option<Rect>,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(data: &'a mut [u8], width: u32, height: u32) -> Self {
+        Self { data, width, height, clip: None }
+    }
+
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    /// Run `draw` with drawing further limited to `clip`, then restore the
+    /// clip as it was
+    pub fn with_clip(&mut self, clip: Rect, draw: impl FnOnce(&mut Self)) {
+        let outer = self.clip;
+        self.clip = Some(outer.map_or(clip, |outer| outer.intersection(&clip)));
+        draw(self);
+        self.clip = outer;
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Replace every pixel inside the clip rect with `paint`, including its alpha
+    pub fn clear(&mut self, paint: &Paint) {
+        if let (Paint::Solid(color), None) = (paint, self.clip) {
+            let pixel = premultiply(*color, 1.0).to_ne_bytes();
+            for chunk in self.data.chunks_exact_mut(4) {
+                chunk.copy_from_slice(&pixel);
+            }
+            return;
+        }
+
+        let area = Rect::new(0, 0, self.width as i32, self.height as i32);
+        let bounds = self.clip.map_or(area, |clip| clip.intersection(&area));
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                let pixel = paint.premultiplied_at(area, x, y);
+                if let Some(offset) = self.offset(x, y) {
+                    self.data[offset..offset + 4].copy_from_slice(&pixel.to_ne_bytes());
+                }
+            }
+        }
+    }
+
+    fn offset(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        if let Some(clip) = self.clip {
+            if x < clip.x || y < clip.y || x >= clip.right() || y >= clip.bottom() {
+                return None;
+            }
+        }
+        let offset = ((y * self.width as i32 + x) * 4) as usize;
+        (offset + 3 < self.data.len()).then_some(offset)
+    }
+
+    /// Composite `color` over the pixel at (x, y)
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        self.blend_pixel(x, y, color, 1.0);
+    }
+
+    /// Composite `color` over the pixel at (x, y), scaled by `coverage` (0.0 - 1.0)
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: u32, coverage: f32) {
+        if coverage <= 0.0 {
+            return;
+        }
+        let Some(offset) = self.offset(x, y) else {
+            return;
+        };
+
+        self.composite(offset, premultiply(color, coverage));
+    }
+
+    fn composite(&mut self, offset: usize, src: u32) {
+        let pixel = &mut self.data[offset..offset + 4];
+        let dst = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        pixel.copy_from_slice(&source_over(src, dst).to_ne_bytes());
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: u32) {
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Anti-aliased filled rectangle with rounded corners
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f32, paint: &Paint) {
+        if rect.is_empty() {
+            return;
+        }
+        let radius = clamp_radius(rect, radius);
+        self.paint_coverage(rect, paint, rect, |px, py| {
+            coverage(rounded_rect_distance(rect, radius, px, py))
+        });
+    }
+
+    /// Anti-aliased outline of a rounded rectangle, drawn inside `rect`
+    pub fn stroke_rounded_rect(&mut self, rect: Rect, radius: f32, border_width: u32, color: u32) {
+        if rect.is_empty() || border_width == 0 {
+            return;
+        }
+        let radius = clamp_radius(rect, radius);
+        let inner = rect.inset(border_width as i32);
+        let inner_radius = (radius - border_width as f32).max(0.0);
+
+        self.fill_coverage(rect, color, |px, py| {
+            let outer = coverage(rounded_rect_distance(rect, radius, px, py));
+            if inner.is_empty() {
+                return outer;
+            }
+            outer - coverage(rounded_rect_distance(inner, inner_radius, px, py))
+        });
+    }
+
+    /// Powerline-style separator filling `rect`: the shape points in
+    /// `direction` and is painted with `shape_paint`, the area around it with
+    /// `fill`. Either may be `None` to leave what is underneath showing.
+    ///
+    /// Each paint comes with the area it spans, so a gradient continues from
+    /// the module it belongs to instead of restarting inside the separator.
+    pub fn fill_separator(
+        &mut self,
+        rect: Rect,
+        shape: Shape,
+        direction: Direction,
+        shape_paint: Option<(&Paint, Rect)>,
+        fill: Option<(&Paint, Rect)>,
+    ) {
+        if rect.is_empty() {
+            return;
+        }
+        let cover = match shape {
+            Shape::Arrow => arrow_coverage(rect, direction),
+            Shape::HalfCircle => half_circle_coverage(rect, direction),
+        };
+        if let Some((paint, area)) = fill {
+            self.paint_coverage(rect, paint, area, |px, py| 1.0 - cover(px, py));
+        }
+        if let Some((paint, area)) = shape_paint {
+            self.paint_coverage(rect, paint, area, &cover);
+        }
+    }
+
+    /// Vertical line `width` pixels wide
+    pub fn vline(&mut self, x: i32, y: i32, height: i32, width: u32, color: u32) {
+        self.fill_rect(Rect::new(x, y, width as i32, height), color);
+    }
+
+    // Like `fill_coverage`, but for a paint spanning `area`
+    fn paint_coverage<F>(&mut self, bounds: Rect, paint: &Paint, area: Rect, cover: F)
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        if let Paint::Solid(color) = paint {
+            self.fill_coverage(bounds, *color, cover);
+            return;
+        }
+
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                let c = cover(x as f32 + 0.5, y as f32 + 0.5).clamp(0.0, 1.0);
+                if c <= 0.0 {
+                    continue;
+                }
+                if let Some(offset) = self.offset(x, y) {
+                    let src = scale(paint.premultiplied_at(area, x, y), c);
+                    self.composite(offset, src);
+                }
+            }
+        }
+    }
+
+    // Evaluate `cover` at every pixel center inside `bounds` and blend accordingly
+    fn fill_coverage<F>(&mut self, bounds: Rect, color: u32, cover: F)
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                let c = cover(x as f32 + 0.5, y as f32 + 0.5);
+                self.blend_pixel(x, y, color, c.clamp(0.0, 1.0));
+            }
+        }
+    }
+}
+
+/// Convert a straight-alpha ARGB color into the premultiplied form the
+/// buffer uses, scaling its alpha by `coverage`
+pub fn premultiply(color: u32, coverage: f32) -> u32 {
+    let alpha = ((color >> 24) & 0xFF) as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    let channel = |shift: u32| ((((color >> shift) & 0xFF) as f32 * alpha).round() as u32) << shift;
+    ((alpha * 255.0).round() as u32) << 24 | channel(16) | channel(8) | channel(0)
+}
+
+// Alpha, red, green, blue of a straight color after premultiplication
+fn premultiplied_channels(color: u32) -> [f32; 4] {
+    let alpha = ((color >> 24) & 0xFF) as f32;
+    let channel = |shift: u32| ((color >> shift) & 0xFF) as f32 * alpha / 255.0;
+    [alpha, channel(16), channel(8), channel(0)]
+}
+
+// Scale every channel of a premultiplied pixel by `coverage`
+fn scale(pixel: u32, coverage: f32) -> u32 {
+    if coverage >= 1.0 {
+        return pixel;
+    }
+    let channel = |shift: u32| ((((pixel >> shift) & 0xFF) as f32 * coverage).round() as u32) << shift;
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
+
+/// Porter-Duff source-over for premultiplied ARGB pixels
+pub fn source_over(src: u32, dst: u32) -> u32 {
+    let inverse = 255 - (src >> 24);
+    if inverse == 0 {
+        return src;
+    }
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xFF;
+        let d = (dst >> shift) & 0xFF;
+        (s + (d * inverse + 127) / 255).min(255) << shift
+    };
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
+
+fn clamp_radius(rect: Rect, radius: f32) -> f32 {
+    radius.max(0.0).min(rect.width as f32 / 2.0).min(rect.height as f32 / 2.0)
+}
+
+// Turn a signed distance (negative inside) into pixel coverage
+fn coverage(distance: f32) -> f32 {
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+// Coverage of a triangle with its base on one edge of `rect` and its tip in
+// the middle of the opposite edge
+fn arrow_coverage(rect: Rect, direction: Direction) -> Box<dyn Fn(f32, f32) -> f32> {
+    let (left, top, w, h) = (rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32);
+    let mid = top + h / 2.0;
+    let (base_x, tip_x) = match direction {
+        Direction::Right => (left, left + w),
+        Direction::Left => (left + w, left),
+    };
+    let slope_len = (w * w + (h / 2.0) * (h / 2.0)).sqrt();
+
+    Box::new(move |px, py| {
+        // Horizontal distance from the base measured towards the tip
+        let along = (px - base_x) * (tip_x - base_x).signum();
+        let dy = (py - mid).abs();
+        // Signed distance to the slanted edges (negative inside)
+        let edge = (along * (h / 2.0) + dy * w - w * (h / 2.0)) / slope_len;
+        coverage(edge.max(-along))
+    })
+}
+
+// Coverage of a half circle whose flat side lies on the edge opposite `direction`
+fn half_circle_coverage(rect: Rect, direction: Direction) -> Box<dyn Fn(f32, f32) -> f32> {
+    let radius = (rect.width as f32).min(rect.height as f32 / 2.0);
+    let cy = rect.y as f32 + rect.height as f32 / 2.0;
+    let cx = match direction {
+        Direction::Right => rect.x as f32,
+        Direction::Left => rect.right() as f32,
+    };
+
+    Box::new(move |px, py| {
+        let dist = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+        coverage(dist - radius)
+    })
+}
+
+// Signed distance from (px, py) to the edge of a rounded rectangle
+fn rounded_rect_distance(rect: Rect, radius: f32, px: f32, py: f32) -> f32 {
+    let half_w = rect.width as f32 / 2.0;
+    let half_h = rect.height as f32 / 2.0;
+    let cx = rect.x as f32 + half_w;
+    let cy = rect.y as f32 + half_h;
+
+    let qx = (px - cx).abs() - (half_w - radius);
+    let qy = (py - cy).abs() - (half_h - radius);
+    let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+    let inside = qx.max(qy).min(0.0);
+    outside + inside - radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u32 = 0xFFFFFFFF;
+
+    // A transparent `width` x `height` buffer, drawn on by `draw`, returning
+    // a function from pixel to alpha
+    fn draw(width: u32, height: u32, draw: impl FnOnce(&mut Canvas)) -> impl Fn(i32, i32) -> u32 {
+        let mut data = vec![0u8; (width * height * 4) as usize];
+        draw(&mut Canvas::new(&mut data, width, height));
+        move |x, y| {
+            let offset = ((y * width as i32 + x) * 4) as usize;
+            u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap()) >> 24
+        }
+    }
+
+    #[test]
+    fn half_transparent_over_opaque() {
+        let src = premultiply(0x80FF0000, 1.0);
+        assert_eq!(src, 0x80800000);
+        assert_eq!(source_over(src, 0xFF0000FF), 0xFF80007F);
+    }
+
+    #[test]
+    fn transparent_sources_leave_the_destination_alone() {
+        let src = premultiply(0x00FFFFFF, 1.0);
+        assert_eq!(src, 0);
+        for dst in [0xFF123456, 0x80402010, 0] {
+            assert_eq!(source_over(src, dst), dst);
+        }
+    }
+
+    #[test]
+    fn coverage_scales_partial_pixels() {
+        let mut data = vec![0u8; 4];
+        let mut canvas = Canvas::new(&mut data, 1, 1);
+        canvas.clear(&Paint::Solid(0xFF000000));
+        // An anti-aliased edge covering a quarter of the pixel
+        canvas.blend_pixel(0, 0, WHITE, 0.25);
+        assert_eq!(u32::from_ne_bytes(data[..4].try_into().unwrap()), 0xFF404040);
+
+        assert_eq!(premultiply(0x80FF0000, 0.5), 0x40400000);
+        assert_eq!(premultiply(WHITE, 0.0), 0);
+    }
+
+    #[test]
+    fn opacity_multiplies_existing_alpha() {
+        assert_eq!(Paint::Solid(0x80FF0000).with_opacity(0.5), Paint::Solid(0x40FF0000));
+        assert_eq!(Paint::Solid(0xCC123456).with_opacity(0.5), Paint::Solid(0x66123456));
+        assert_eq!(Paint::Solid(0x80FF0000).with_opacity(2.0), Paint::Solid(0x80FF0000));
+
+        let gradient = Paint::LinearGradient {
+            angle: 90.0,
+            stops: vec![
+                ColorStop { offset: 0.0, color: WHITE },
+                ColorStop { offset: 1.0, color: 0x80000000 },
+            ],
+        };
+        let Paint::LinearGradient { stops, .. } = gradient.with_opacity(0.5) else {
+            panic!("opacity changed the kind of paint");
+        };
+        let colors: Vec<u32> = stops.iter().map(|s| s.color).collect();
+        assert_eq!(colors, [0x80FFFFFF, 0x40000000]);
+    }
+
+    #[test]
+    fn gradients_interpolate_premultiplied() {
+        // Left to right from transparent red to blue
+        let paint = Paint::LinearGradient {
+            angle: 90.0,
+            stops: vec![
+                ColorStop { offset: 0.0, color: 0x00FF0000 },
+                ColorStop { offset: 1.0, color: 0xFE0000FF },
+            ],
+        };
+        let area = Rect::new(0, 0, 101, 1);
+
+        // Past either end is the end color, and the middle pixel is halfway
+        // without any of the transparent red bleeding in
+        assert_eq!(paint.premultiplied_at(area, -1, 0), 0);
+        assert_eq!(paint.premultiplied_at(area, 101, 0), 0xFE0000FE);
+        for y in 0..4 {
+            assert_eq!(paint.premultiplied_at(area, 50, y), 0x7F00007F);
+        }
+    }
+
+    #[test]
+    fn nested_clips_intersect_and_restore() {
+        let alpha = draw(10, 1, |canvas| {
+            canvas.set_clip(Some(Rect::new(2, 0, 6, 1)));
+            canvas.with_clip(Rect::new(0, 0, 4, 1), |canvas| canvas.fill_rect(Rect::new(0, 0, 10, 1), WHITE));
+            canvas.fill_rect(Rect::new(7, 0, 3, 1), WHITE);
+        });
+
+        let row: Vec<_> = (0..10).map(|x| alpha(x, 0)).collect();
+        assert_eq!(row, [0, 0, 255, 255, 0, 0, 0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn rounded_corners_are_cut_and_anti_aliased() {
+        let rect = Rect::new(0, 0, 20, 10);
+        let alpha = draw(20, 10, |canvas| canvas.fill_rounded_rect(rect, 3.0, &Paint::Solid(WHITE)));
+
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(19, 9), 0);
+        assert!((1..255).contains(&alpha(1, 0)));
+        // Straight edges and the middle are fully covered
+        assert_eq!(alpha(0, 5), 255);
+        assert_eq!(alpha(10, 0), 255);
+        assert_eq!(alpha(10, 5), 255);
+
+        let square = draw(20, 10, |canvas| canvas.fill_rounded_rect(rect, 0.0, &Paint::Solid(WHITE)));
+        assert_eq!(square(0, 0), 255);
+    }
+
+    #[test]
+    fn strokes_cover_only_the_border() {
+        let rect = Rect::new(0, 0, 20, 10);
+        let alpha = draw(20, 10, |canvas| canvas.stroke_rounded_rect(rect, 3.0, 2, WHITE));
+
+        assert_eq!((alpha(0, 5), alpha(1, 5), alpha(2, 5)), (255, 255, 0));
+        assert_eq!((alpha(10, 0), alpha(10, 1), alpha(10, 2)), (255, 255, 0));
+        assert_eq!(alpha(10, 5), 0);
+        assert_eq!(alpha(0, 0), 0);
+    }
+
+    #[test]
+    fn separators_point_the_right_way() {
+        let rect = Rect::new(0, 0, 10, 10);
+        let white = Paint::Solid(WHITE);
+
+        // Base on the left edge, tip in the middle of the right one
+        let arrow = draw(10, 10, |canvas| {
+            canvas.fill_separator(rect, Shape::Arrow, Direction::Right, Some((&white, rect)), None)
+        });
+        assert_eq!(arrow(0, 5), 255);
+        assert_eq!(arrow(9, 0), 0);
+        assert_eq!(arrow(9, 9), 0);
+        assert!(arrow(9, 5) > 0);
+
+        // The fill covers what the shape doesn't
+        let filled = draw(10, 10, |canvas| {
+            canvas.fill_separator(rect, Shape::Arrow, Direction::Left, None, Some((&white, rect)))
+        });
+        assert_eq!(filled(0, 0), 255);
+        assert_eq!(filled(9, 5), 0);
+
+        // Flat side on the right edge
+        let half_circle = draw(10, 10, |canvas| {
+            canvas.fill_separator(rect, Shape::HalfCircle, Direction::Left, Some((&white, rect)), None)
+        });
+        assert_eq!(half_circle(9, 5), 255);
+        assert!(half_circle(9, 0) > 0);
+        assert_eq!(half_circle(5, 0), 0);
+        assert_eq!(half_circle(3, 5), 0);
+    }
+}
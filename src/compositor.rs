@@ -0,0 +1,157 @@
+// What the bar needs from a compositor, whichever one is running
+//
+// Each backend speaks its own IPC and translates to the types below, so the
+// workspace, window, taskbar and keyboard layout modules don't care whether
+// niri, sway or Hyprland is on the other end.
+
+use crate::hyprland::HyprlandIpc;
+use crate::niri::NiriIpc;
+use crate::sway::SwayIpc;
+use std::ops::Range;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub id: u64,
+    // Position shown on the bar, 1-based
+    pub idx: u32,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    // Shown on its output, though maybe not focused
+    pub is_active: bool,
+    pub is_focused: bool,
+    pub is_urgent: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub workspace_id: Option<u64>,
+    pub is_focused: bool,
+}
+
+/// Where one workspace's label sits in the workspace summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceEntry {
+    pub workspace_id: u64,
+    pub graphemes: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardLayouts {
+    pub names: Vec<String>,
+    pub current_idx: usize,
+}
+
+/// Things a click can ask the compositor to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    FocusWorkspace { id: u64 },
+    // Hide a workspace without showing another, where the compositor allows it
+    DeactivateWorkspace { id: u64 },
+    FocusWindow { id: u64 },
+    // Minimize a window, or restore it if it already is
+    MinimizeWindow { id: u64 },
+    CloseWindow { id: u64 },
+    NextLayout,
+    PrevLayout,
+}
+
+pub trait Compositor: Send {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>>;
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>>;
+
+    fn focused_window(&self) -> anyhow::Result<Option<Window>> {
+        Ok(self.windows()?.into_iter().find(|w| w.is_focused))
+    }
+
+    fn keyboard_layouts(&self) -> anyhow::Result<KeyboardLayouts> {
+        anyhow::bail!("keyboard layouts aren't available from this compositor")
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()>;
+
+    /// Whether `action` can work here at all, so clicks are only bound to
+    /// actions the compositor won't refuse
+    fn supports(&self, action: &Action) -> bool {
+        !matches!(action, Action::DeactivateWorkspace { .. } | Action::MinimizeWindow { .. })
+    }
+
+    /// Call `on_event` from a background thread whenever something the bar
+    /// shows may have changed, until it returns false
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>);
+
+    /// Text for the workspaces module, along with where each workspace is
+    fn workspace_summary(&self) -> (String, Vec<WorkspaceEntry>) {
+        match self.workspaces() {
+            Ok(workspaces) => summarize(workspaces),
+            Err(_) => (String::from("WS ?"), Vec::new()),
+        }
+    }
+}
+
+/// The compositor this session runs under, judged by the sockets it
+/// advertises in the environment
+pub fn detect() -> Option<Box<dyn Compositor>> {
+    if let Some(niri) = NiriIpc::new() {
+        return Some(Box::new(niri));
+    }
+    if let Some(sway) = SwayIpc::new() {
+        return Some(Box::new(sway));
+    }
+    if let Some(hyprland) = HyprlandIpc::new() {
+        return Some(Box::new(hyprland));
+    }
+    None
+}
+
+/// Run `follow` on a background thread, starting it again a second after
+/// every disconnect. `follow` hands events to `on_event` until the connection
+/// drops and returns false once `on_event` asks to stop; `stale` then tells
+/// `on_event` that whatever it has seen may be out of date.
+pub(crate) fn spawn_reconnecting<E, F, S>(mut follow: F, mut on_event: E, stale: S)
+where
+    E: Send + 'static,
+    F: FnMut(&mut E) -> bool + Send + 'static,
+    S: Fn(&mut E) -> bool + Send + 'static,
+{
+    std::thread::spawn(move || loop {
+        let keep_going = follow(&mut on_event);
+
+        if !keep_going || !stale(&mut on_event) {
+            return;
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    });
+}
+
+// "1 [2] 3", the focused workspace in brackets
+pub fn summarize(mut workspaces: Vec<Workspace>) -> (String, Vec<WorkspaceEntry>) {
+    if workspaces.is_empty() {
+        return (String::from("Empty"), Vec::new());
+    }
+    workspaces.sort_by_key(|w| w.idx);
+
+    let mut text = String::new();
+    let mut entries = Vec::new();
+    for ws in workspaces {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let label = if ws.is_focused {
+            format!("[{}]", ws.idx)
+        } else {
+            format!("{}", ws.idx)
+        };
+        // Labels are ASCII, so bytes and graphemes agree
+        entries.push(WorkspaceEntry {
+            workspace_id: ws.id,
+            graphemes: text.len()..text.len() + label.len(),
+        });
+        text.push_str(&label);
+    }
+    (text, entries)
+}
@@ -1,6 +1,10 @@
+use crate::canvas::{ColorStop, Paint};
+use crate::layout::Align;
+use crate::modules::{parse_bytes, ModuleState};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
@@ -21,6 +25,12 @@ pub struct Config {
     
     #[serde(default)]
     pub module_config: ModuleConfig,
+    
+    #[serde(default)]
+    pub module_style: HashMap<String, ModuleStyle>,
+    
+    #[serde(default)]
+    pub module_layout: HashMap<String, ModuleLayout>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -48,9 +58,166 @@ pub struct Style {
     
     #[serde(default = "default_font_size")]
     pub font_size: u32,
+    
+    // Multiplies the background alpha, 1.0 = opaque
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    
+    // Module "pills" - no background means plain text modules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_background: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_border: Option<String>,
+    
+    #[serde(default)]
+    pub module_border_width: u32,
+    
+    #[serde(default)]
+    pub module_radius: u32,
+    
+    // Horizontal space between a module's text and its pill edge
+    #[serde(default)]
+    pub module_padding: u32,
+    
+    // Vertical space between the pill and the bar edge
+    #[serde(default = "default_module_margin")]
+    pub module_margin: u32,
+    
+    #[serde(default)]
+    pub separator: SeparatorConfig,
+}
+
+/// Per-module overrides, e.g. `[module_style.clock]`, with optional
+/// `[module_style.<name>.warning]` and `.critical` tables on top
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ModuleStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<StateStyle>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical: Option<StateStyle>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct StateStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+}
+
+impl ModuleStyle {
+    /// The `warning`/`critical` table for `state`, if configured
+    pub fn state_override(&self, state: ModuleState) -> Option<&StateStyle> {
+        match state {
+            ModuleState::Normal => None,
+            ModuleState::Warning => self.warning.as_ref(),
+            ModuleState::Critical => self.critical.as_ref(),
+        }
+    }
+    
+    /// Effective style in `state`, falling back to the module's base style
+    pub fn for_state(&self, state: ModuleState) -> StateStyle {
+        let base = StateStyle {
+            background: self.background.clone(),
+            foreground: self.foreground.clone(),
+            border: self.border.clone(),
+        };
+        match self.state_override(state) {
+            Some(over) => StateStyle {
+                background: over.background.clone().or(base.background),
+                foreground: over.foreground.clone().or(base.foreground),
+                border: over.border.clone().or(base.border),
+            },
+            None => base,
+        }
+    }
+}
+
+/// Per-module sizing, e.g. `[module_layout.clock]`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModuleLayout {
+    // Narrowest the module's text may get, in pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    
+    // Widest the module's text may get, in pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    
+    // Share of the free space the module grows into, 0 = natural width
+    #[serde(default)]
+    pub flex: u32,
+    
+    // Position of the text inside a box wider than it
+    #[serde(default)]
+    pub align: Align,
+    
+    // What to do with text that doesn't fit
+    #[serde(default)]
+    pub overflow: Overflow,
+    
+    // Scroll speed in pixels per second for `overflow = "marquee"`
+    #[serde(default = "default_marquee_speed")]
+    pub marquee_speed: u32,
+    
+    // When regions collide, lower priority modules are shrunk first and,
+    // once at their minimum width, hidden first
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    #[default]
+    Ellipsis,
+    Marquee,
+    Clip,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SeparatorKind {
+    #[default]
+    None,
+    Glyph,
+    Line,
+    Arrow,
+    Round,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SeparatorConfig {
+    #[serde(default)]
+    pub kind: SeparatorKind,
+    
+    #[serde(default = "default_separator_glyph")]
+    pub glyph: String,
+    
+    // Defaults to the foreground color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    
+    #[serde(default = "default_separator_width")]
+    pub width: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ModuleConfig {
     #[serde(default)]
     pub workspaces: WorkspacesConfig,
@@ -63,6 +230,30 @@ pub struct ModuleConfig {
     
     #[serde(default)]
     pub clock: ClockConfig,
+    
+    #[serde(default)]
+    pub window: WindowConfig,
+    
+    #[serde(default)]
+    pub taskbar: TaskbarConfig,
+    
+    #[serde(default)]
+    pub keyboard_layout: KeyboardLayoutConfig,
+    
+    #[serde(default)]
+    pub cpu: CpuConfig,
+    
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    
+    #[serde(default)]
+    pub disk: DiskConfig,
+    
+    #[serde(default)]
+    pub temperature: TemperatureConfig,
+    
+    #[serde(default)]
+    pub backlight: BacklightConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -91,11 +282,63 @@ pub struct BatteryConfig {
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct NetworkConfig {
+    // {icon} ("WiFi" or "ETH"), {ifname}, {ipv4}, {ipv6}, {gateway}, {mtu},
+    // and the {down} and {up} rates per second, with an optional unit as for
+    // memory, e.g. {down:KiB}
     #[serde(default = "default_network_format")]
     pub format: String,
     
+    // Shown while no interface is up
+    #[serde(default = "default_network_format_disconnected")]
+    pub format_disconnected: String,
+    
     #[serde(default = "default_true")]
     pub show_icon: bool,
+    
+    // Rates per second, the faster of down and up; unset never warns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning_threshold: Option<ByteSize>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical_threshold: Option<ByteSize>,
+    
+    // Preferred over the interfaces found up, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<NetworkInterface>,
+}
+
+/// One `[[module_config.network.interfaces]]` entry; unset fields fall back
+/// to the module's
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct NetworkInterface {
+    pub name: String,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning_threshold: Option<ByteSize>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical_threshold: Option<ByteSize>,
+}
+
+/// A number of bytes, or a string with a unit such as "5MiB" or "10MB"
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ByteSize {
+    Bytes(u64),
+    WithUnit(String),
+}
+
+impl ByteSize {
+    /// None for a unit that isn't one of {used:...}'s
+    pub fn bytes(&self) -> Option<f64> {
+        match self {
+            ByteSize::Bytes(bytes) => Some(*bytes as f64),
+            ByteSize::WithUnit(text) => parse_bytes(text),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -104,6 +347,250 @@ pub struct ClockConfig {
     pub format: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WindowConfig {
+    // {title} and {app_id} are replaced
+    #[serde(default = "default_window_format")]
+    pub format: String,
+    
+    // In graphemes, 0 for no limit
+    #[serde(default)]
+    pub max_length: u32,
+    
+    // Shown when no window is focused; the module hides if this is empty
+    #[serde(default)]
+    pub empty: String,
+    
+    #[serde(default)]
+    pub rewrite: Vec<RewriteRule>,
+}
+
+/// Replace `pattern` in the title of windows whose app_id matches `app_id`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RewriteRule {
+    // Regex; the rule applies to every window when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    
+    pub pattern: String,
+    
+    // May refer to capture groups as $1 or ${1}
+    #[serde(default)]
+    pub replacement: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TaskbarConfig {
+    #[serde(default)]
+    pub scope: TaskbarScope,
+    
+    #[serde(default)]
+    pub label: TaskbarLabel,
+    
+    // Graphemes kept when abbreviating an app_id
+    #[serde(default = "default_abbrev_length")]
+    pub abbrev_length: u32,
+    
+    // Per-window limit in graphemes, 0 for no limit
+    #[serde(default)]
+    pub max_length: u32,
+    
+    // app_id -> label, e.g. an icon glyph, used instead of `label`
+    #[serde(default)]
+    pub icons: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskbarScope {
+    // Windows on the focused workspace
+    #[default]
+    Workspace,
+    All,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskbarLabel {
+    // "org.mozilla.firefox" -> "fir"
+    #[default]
+    Abbrev,
+    AppId,
+    Title,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KeyboardLayoutConfig {
+    // {label} is the short label, {name} the layout name niri reports
+    #[serde(default = "default_keyboard_layout_format")]
+    pub format: String,
+    
+    // Layout name -> short label, e.g. "English (US)" = "us"; unmapped
+    // layouts show their full name
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CpuConfig {
+    // {usage} overall and {usage0}, {usage1}, ... per core, in percent;
+    // {graph} one bar per core; {avg_frequency} and {max_frequency} in GHz;
+    // {load1}, {load5} and {load15} load averages
+    #[serde(default = "default_cpu_format")]
+    pub format: String,
+    
+    // Overall usage in percent at which the module turns warning/critical
+    #[serde(default = "default_cpu_warning")]
+    pub warning_threshold: u32,
+    
+    #[serde(default = "default_cpu_critical")]
+    pub critical_threshold: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MemoryConfig {
+    // {percentage} and {swap_percentage} of RAM and swap in use; sizes are
+    // {used}, {available}, {total}, {free}, {buffers}, {cached}, {swap_used},
+    // {swap_total}, {zram_used} and {zram_total}, auto-scaled like "7.4GiB"
+    // or in a fixed unit without the suffix, e.g. {used:GiB}
+    #[serde(default = "default_memory_format")]
+    pub format: String,
+    
+    // Shown while the pointer is over the module, with the same placeholders;
+    // empty for none
+    #[serde(default = "default_memory_tooltip_format")]
+    pub tooltip_format: String,
+    
+    // Percentage of RAM in use at which the module turns warning/critical
+    #[serde(default = "default_memory_warning")]
+    pub warning_threshold: u32,
+    
+    #[serde(default = "default_memory_critical")]
+    pub critical_threshold: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DiskConfig {
+    // {mount} (label or path), {path}, {percentage} in use, and sizes {used},
+    // {free} and {total} with an optional unit as for memory, e.g. {free:GiB}
+    #[serde(default = "default_disk_format")]
+    pub format: String,
+    
+    // Percentage in use at which a mount turns warning/critical
+    #[serde(default = "default_disk_warning")]
+    pub warning_threshold: u32,
+    
+    #[serde(default = "default_disk_critical")]
+    pub critical_threshold: u32,
+    
+    #[serde(default = "default_disk_mounts")]
+    pub mounts: Vec<DiskMount>,
+    
+    #[serde(default)]
+    pub display: DiskDisplay,
+    
+    // Between mounts shown together
+    #[serde(default = "default_disk_separator")]
+    pub separator: String,
+    
+    // Seconds each mount is shown for with `display = "rotate"`
+    #[serde(default = "default_disk_rotate_interval")]
+    pub rotate_interval: u32,
+}
+
+/// One `[[module_config.disk.mounts]]` entry; unset fields fall back to the
+/// module's
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DiskMount {
+    pub path: String,
+    
+    // Shown as {mount} instead of the path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning_threshold: Option<u32>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical_threshold: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskDisplay {
+    // Every mount, joined by `separator`
+    #[default]
+    All,
+    // One mount at a time, in turn
+    Rotate,
+    // Only mounts at or over their warning threshold; hidden otherwise
+    AboveThreshold,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TemperatureConfig {
+    // {temperature} in `unit`, and {unit} itself, "C" or "F"
+    #[serde(default = "default_temperature_format")]
+    pub format: String,
+    
+    // "<hwmon name>/<label>" such as "k10temp/Tctl" or "coretemp/Package id 0",
+    // an hwmon name alone for its first sensor, or a thermal zone type such as
+    // "x86_pkg_temp". Unset picks the first sensor found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<String>,
+    
+    #[serde(default)]
+    pub unit: TemperatureUnit,
+    
+    // In `unit`; unset uses the limits the sensor reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning_threshold: Option<f64>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical_threshold: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "C",
+            TemperatureUnit::Fahrenheit => "F",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BacklightConfig {
+    // {percentage}, and the raw {brightness} out of {max} for {device}
+    #[serde(default = "default_backlight_format")]
+    pub format: String,
+    
+    // A directory in /sys/class/backlight such as "intel_backlight"; unset
+    // picks the first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    
+    // Percentage points per scroll notch
+    #[serde(default = "default_backlight_step")]
+    pub step: u32,
+    
+    // Run with `sh -c` to set the brightness instead of asking logind, with
+    // {percentage}, the raw {value} and {device} filled in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
 // Default functions
 fn default_height() -> u32 { 30 }
 fn default_bg_color() -> String { "#1e1e2e".to_string() }
@@ -114,14 +601,47 @@ fn default_critical_color() -> String { "#f38ba8".to_string() }
 fn default_padding() -> u32 { 10 }
 fn default_spacing() -> u32 { 15 }
 fn default_font_size() -> u32 { 12 }
+fn default_opacity() -> f32 { 1.0 }
+fn default_module_margin() -> u32 { 3 }
+fn default_marquee_speed() -> u32 { 30 }
+fn default_separator_glyph() -> String { "|".to_string() }
+fn default_separator_width() -> u32 { 1 }
 
 fn default_ws_format() -> String { "{idx}".to_string() }
 fn default_battery_format() -> String { "{icon} {percentage}%".to_string() }
 fn default_network_format() -> String { "{icon} {ifname}".to_string() }
+fn default_network_format_disconnected() -> String { "NET Down".to_string() }
 fn default_clock_format() -> String { "%H:%M:%S".to_string() }
+fn default_window_format() -> String { "{title}".to_string() }
+fn default_abbrev_length() -> u32 { 3 }
+fn default_keyboard_layout_format() -> String { "{label}".to_string() }
+fn default_cpu_format() -> String { "CPU {usage}%".to_string() }
+fn default_memory_format() -> String { "RAM {percentage}%".to_string() }
+fn default_memory_tooltip_format() -> String { "buf {buffers} cache {cached} free {free}".to_string() }
+fn default_disk_format() -> String { "{mount} {percentage}%".to_string() }
+fn default_temperature_format() -> String { "{temperature}{unit}".to_string() }
+fn default_backlight_format() -> String { "BL {percentage}%".to_string() }
+fn default_disk_separator() -> String { " ".to_string() }
+fn default_disk_mounts() -> Vec<DiskMount> {
+    vec![DiskMount {
+        path: "/".to_string(),
+        label: None,
+        format: None,
+        warning_threshold: None,
+        critical_threshold: None,
+    }]
+}
 
 fn default_battery_warning() -> u32 { 30 }
 fn default_battery_critical() -> u32 { 15 }
+fn default_cpu_warning() -> u32 { 70 }
+fn default_cpu_critical() -> u32 { 90 }
+fn default_memory_warning() -> u32 { 80 }
+fn default_memory_critical() -> u32 { 95 }
+fn default_disk_warning() -> u32 { 80 }
+fn default_disk_critical() -> u32 { 90 }
+fn default_disk_rotate_interval() -> u32 { 5 }
+fn default_backlight_step() -> u32 { 5 }
 fn default_true() -> bool { true }
 
 impl Default for Style {
@@ -135,6 +655,39 @@ impl Default for Style {
             padding: default_padding(),
             module_spacing: default_spacing(),
             font_size: default_font_size(),
+            opacity: default_opacity(),
+            module_background: None,
+            module_border: None,
+            module_border_width: 0,
+            module_radius: 0,
+            module_padding: 0,
+            module_margin: default_module_margin(),
+            separator: SeparatorConfig::default(),
+        }
+    }
+}
+
+impl Default for ModuleLayout {
+    fn default() -> Self {
+        Self {
+            min_width: None,
+            max_width: None,
+            flex: 0,
+            align: Align::default(),
+            overflow: Overflow::Ellipsis,
+            marquee_speed: default_marquee_speed(),
+            priority: 0,
+        }
+    }
+}
+
+impl Default for SeparatorConfig {
+    fn default() -> Self {
+        Self {
+            kind: SeparatorKind::None,
+            glyph: default_separator_glyph(),
+            color: None,
+            width: default_separator_width(),
         }
     }
 }
@@ -163,7 +716,11 @@ impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
             format: default_network_format(),
+            format_disconnected: default_network_format_disconnected(),
             show_icon: true,
+            warning_threshold: None,
+            critical_threshold: None,
+            interfaces: Vec::new(),
         }
     }
 }
@@ -176,13 +733,92 @@ impl Default for ClockConfig {
     }
 }
 
-impl Default for ModuleConfig {
+impl Default for TaskbarConfig {
+    fn default() -> Self {
+        Self {
+            scope: TaskbarScope::default(),
+            label: TaskbarLabel::default(),
+            abbrev_length: default_abbrev_length(),
+            max_length: 0,
+            icons: HashMap::new(),
+        }
+    }
+}
+
+impl Default for KeyboardLayoutConfig {
+    fn default() -> Self {
+        Self {
+            format: default_keyboard_layout_format(),
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl Default for CpuConfig {
     fn default() -> Self {
         Self {
-            workspaces: WorkspacesConfig::default(),
-            battery: BatteryConfig::default(),
-            network: NetworkConfig::default(),
-            clock: ClockConfig::default(),
+            format: default_cpu_format(),
+            warning_threshold: default_cpu_warning(),
+            critical_threshold: default_cpu_critical(),
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            format: default_memory_format(),
+            tooltip_format: default_memory_tooltip_format(),
+            warning_threshold: default_memory_warning(),
+            critical_threshold: default_memory_critical(),
+        }
+    }
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            format: default_disk_format(),
+            warning_threshold: default_disk_warning(),
+            critical_threshold: default_disk_critical(),
+            mounts: default_disk_mounts(),
+            display: DiskDisplay::default(),
+            separator: default_disk_separator(),
+            rotate_interval: default_disk_rotate_interval(),
+        }
+    }
+}
+
+impl Default for TemperatureConfig {
+    fn default() -> Self {
+        Self {
+            format: default_temperature_format(),
+            sensor: None,
+            unit: TemperatureUnit::default(),
+            warning_threshold: None,
+            critical_threshold: None,
+        }
+    }
+}
+
+impl Default for BacklightConfig {
+    fn default() -> Self {
+        Self {
+            format: default_backlight_format(),
+            device: None,
+            step: default_backlight_step(),
+            command: None,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            format: default_window_format(),
+            max_length: 0,
+            empty: String::new(),
+            rewrite: Vec::new(),
         }
     }
 }
@@ -196,6 +832,8 @@ impl Default for Config {
             modules_center: vec![],
             modules_right: vec!["network".to_string(), "battery".to_string(), "clock".to_string()],
             module_config: ModuleConfig::default(),
+            module_style: HashMap::new(),
+            module_layout: HashMap::new(),
         }
     }
 }
@@ -233,6 +871,18 @@ impl Config {
         Self::default()
     }
     
+    /// Load a specific config file, failing instead of falling back to defaults
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&contents)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+    
+    pub fn from_toml(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+    
     fn get_config_path() -> PathBuf {
         if let Ok(config_home) = std::env::var("XDG_CONFIG_HOME") {
             PathBuf::from(config_home).join("oxidebar").join("config.toml")
@@ -243,23 +893,192 @@ impl Config {
         }
     }
     
+    /// Bar background with `style.opacity` applied to its alpha
+    pub fn background_paint(&self) -> Paint {
+        self.parse_paint(&self.style.background)
+            .with_opacity(self.style.opacity)
+    }
+    
+    /// Parse a background value: either a color or a CSS-like
+    /// `linear-gradient([<angle> | to <side>,] <color> [<pos>%], ...)`
+    pub fn parse_paint(&self, value: &str) -> Paint {
+        let value = value.trim();
+        let Some(args) = value.strip_prefix("linear-gradient(") else {
+            return Paint::Solid(self.parse_color(value));
+        };
+        // A malformed gradient falls back to white, as a bad color does
+        args.strip_suffix(')')
+            .and_then(parse_gradient)
+            .unwrap_or_else(|| Paint::Solid(self.parse_color("")))
+    }
+    
     pub fn parse_color(&self, color_str: &str) -> u32 {
-        // Parse hex color like "#1e1e2e" to ARGB
-        let color_str = color_str.trim_start_matches('#');
-        
-        if color_str.len() == 6 {
-            // RGB format - add full opacity
-            if let Ok(rgb) = u32::from_str_radix(color_str, 16) {
-                return 0xFF000000 | rgb;
-            }
-        } else if color_str.len() == 8 {
-            // ARGB format
-            if let Ok(argb) = u32::from_str_radix(color_str, 16) {
-                return argb;
+        // Default to white on parse error
+        parse_hex(color_str).unwrap_or(0xFFFFFFFF)
+    }
+}
+
+// Parse a hex color: "#rrggbb" is opaque, "#aarrggbb" is ARGB
+fn parse_hex(value: &str) -> Option<u32> {
+    let digits = value.trim_start_matches('#');
+    let color = u32::from_str_radix(digits, 16).ok()?;
+    match digits.len() {
+        6 => Some(0xFF000000 | color),
+        8 => Some(color),
+        _ => None,
+    }
+}
+
+// Parse the arguments of `linear-gradient(...)`; None if any are malformed
+fn parse_gradient(args: &str) -> Option<Paint> {
+    let mut args: Vec<&str> = args.split(',').map(str::trim).collect();
+    
+    // Default direction is top to bottom, as in CSS
+    let mut angle = 180.0;
+    if let Some(parsed) = args.first().and_then(|first| parse_angle(first)) {
+        angle = parsed;
+        args.remove(0);
+    }
+    
+    let mut stops: Vec<(u32, Option<f32>)> = args
+        .iter()
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            let mut parts = arg.split_whitespace();
+            let color = parse_hex(parts.next()?)?;
+            let offset = match parts.next() {
+                Some(offset) => Some(offset.strip_suffix('%')?.parse::<f32>().ok()? / 100.0),
+                None => None,
+            };
+            parts.next().is_none().then_some((color, offset))
+        })
+        .collect::<Option<_>>()?;
+    
+    match stops.len() {
+        0 => return None,
+        1 => return Some(Paint::Solid(stops[0].0)),
+        _ => {}
+    }
+    
+    // Stops without a position are spread evenly between their neighbours
+    let last = stops.len() - 1;
+    stops[0].1.get_or_insert(0.0);
+    stops[last].1.get_or_insert(1.0);
+    let mut i = 1;
+    while i < last {
+        if stops[i].1.is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i - 1;
+        let end = (i..=last).find(|&j| stops[j].1.is_some()).unwrap_or(last);
+        let from = stops[start].1.unwrap_or(0.0);
+        let to = stops[end].1.unwrap_or(1.0);
+        let steps = (end - start) as f32;
+        for (j, stop) in stops.iter_mut().enumerate().take(end).skip(i) {
+            stop.1 = Some(from + (to - from) * (j - start) as f32 / steps);
+        }
+        i = end;
+    }
+    
+    // Positions must never go backwards
+    let mut previous = 0.0f32;
+    let stops = stops
+        .into_iter()
+        .map(|(color, offset)| {
+            previous = offset.unwrap_or(previous).max(previous);
+            ColorStop { offset: previous, color }
+        })
+        .collect();
+    
+    Some(Paint::LinearGradient { angle, stops })
+}
+
+// Parse a gradient direction: "90deg", "0.25turn", "1.57rad" or "to right"
+fn parse_angle(value: &str) -> Option<f32> {
+    if let Some(side) = value.strip_prefix("to ") {
+        return match side.trim() {
+            "top" => Some(0.0),
+            "right" => Some(90.0),
+            "bottom" => Some(180.0),
+            "left" => Some(270.0),
+            _ => None,
+        };
+    }
+    if let Some(deg) = value.strip_suffix("deg") {
+        deg.trim().parse().ok()
+    } else if let Some(turn) = value.strip_suffix("turn") {
+        turn.trim().parse::<f32>().ok().map(|t| t * 360.0)
+    } else if let Some(rad) = value.strip_suffix("rad") {
+        rad.trim().parse::<f32>().ok().map(f32::to_degrees)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Angle and (offset, color) stops of a value that must parse as a gradient
+    fn gradient(value: &str) -> (f32, Vec<(f32, u32)>) {
+        match Config::default().parse_paint(value) {
+            Paint::LinearGradient { angle, stops } => {
+                (angle, stops.iter().map(|s| (s.offset, s.color)).collect())
             }
+            paint => panic!("{} parsed as {:?}", value, paint),
+        }
+    }
+
+    #[test]
+    fn gradient_directions() {
+        assert_eq!(gradient("linear-gradient(to right, #000000, #ffffff)").0, 90.0);
+        assert_eq!(gradient("linear-gradient(45deg, #000000, #ffffff)").0, 45.0);
+        assert_eq!(gradient("linear-gradient(0.75turn, #000000, #ffffff)").0, 270.0);
+        let (angle, _) = gradient("linear-gradient(1.5708rad, #000000, #ffffff)");
+        assert!((angle - 90.0).abs() < 0.01);
+        // Top to bottom unless told otherwise
+        assert_eq!(gradient("linear-gradient(#000000, #ffffff)").0, 180.0);
+
+        assert_eq!(parse_angle("to top"), Some(0.0));
+        assert_eq!(parse_angle("-90deg"), Some(-90.0));
+        for malformed in ["to sideways", "90", "deg", "quarterturn", "#000000"] {
+            assert_eq!(parse_angle(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn gradient_stops_spread_and_never_go_backwards() {
+        let (_, stops) = gradient("linear-gradient(#000000, #111111, #80222222 80%, #333333)");
+        assert_eq!(
+            stops,
+            [(0.0, 0xFF000000), (0.4, 0xFF111111), (0.8, 0x80222222), (1.0, 0xFF333333)]
+        );
+
+        let (_, stops) = gradient("linear-gradient(to right, #000000 60%, #111111 20%, #222222)");
+        let offsets: Vec<f32> = stops.iter().map(|s| s.0).collect();
+        assert_eq!(offsets, [0.6, 0.6, 1.0]);
+    }
+
+    #[test]
+    fn single_stops_and_malformed_gradients() {
+        let config = Config::default();
+        assert_eq!(config.parse_paint("linear-gradient(to right, #123456)"), Paint::Solid(0xFF123456));
+        assert_eq!(config.parse_paint("linear-gradient(#80123456 50%)"), Paint::Solid(0x80123456));
+        assert_eq!(config.parse_paint(" #80123456 "), Paint::Solid(0x80123456));
+
+        // Like a bad color, anything malformed comes out white
+        for malformed in [
+            "linear-gradient(#000000, #ffffff",
+            "linear-gradient()",
+            "linear-gradient(90deg)",
+            "linear-gradient(to sideways, #000000, #ffffff)",
+            "linear-gradient(#00000, #ffffff)",
+            "linear-gradient(#000000 half, #ffffff)",
+            "linear-gradient(#000000 50, #ffffff)",
+            "linear-gradient(#000000 10% 20%, #ffffff)",
+        ] {
+            assert_eq!(config.parse_paint(malformed), Paint::Solid(0xFFFFFFFF), "{}", malformed);
         }
-        
-        // Default to white on parse error
-        0xFFFFFFFF
     }
 }
@@ -0,0 +1,418 @@
+// Workspaces over the ext-workspace-v1 Wayland protocol
+//
+// Any compositor that advertises ext_workspace_manager_v1 describes its
+// workspaces on the Wayland connection itself, so no IPC socket is needed.
+// Events arrive on the bar's own event queue; the bar reads the latest state
+// whenever it draws.
+
+use anyhow::bail;
+use oxidebar::compositor::{Action, Compositor, Window, Workspace};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wayland_client::{
+    event_created_child, globals::GlobalList, protocol::wl_output, Connection, Dispatch, Proxy,
+    QueueHandle, WEnum,
+};
+
+use protocol::ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1};
+use protocol::ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1, WorkspaceCapabilities};
+use protocol::ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1};
+
+pub mod protocol {
+    #![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+    #![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+    #![allow(missing_docs, clippy::all)]
+
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/ext-workspace-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/ext-workspace-v1.xml");
+}
+
+/// Lets the protocol handlers find the shared state in the app
+pub trait ExtWorkspaceHandler {
+    fn ext_workspaces(&self) -> Option<&ExtWorkspaces>;
+}
+
+// What the compositor has said about one workspace
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Info {
+    name: Option<String>,
+    coordinates: Vec<u32>,
+    active: bool,
+    urgent: bool,
+    hidden: bool,
+    // Protocol id of the group it belongs to
+    group: Option<u32>,
+}
+
+struct Entry {
+    handle: ExtWorkspaceHandleV1,
+    info: Info,
+    capabilities: WorkspaceCapabilities,
+}
+
+#[derive(Default)]
+struct Group {
+    // Protocol ids of its wl_outputs
+    outputs: Vec<u32>,
+}
+
+struct Shared {
+    manager: ExtWorkspaceManagerV1,
+    // Keyed by protocol id, which also serves as the workspace id
+    workspaces: Vec<(u32, Entry)>,
+    groups: HashMap<u32, Group>,
+    output_names: HashMap<u32, String>,
+    on_done: Option<Box<dyn FnMut() -> bool + Send>>,
+}
+
+/// The workspaces the compositor last described; clones share the state
+#[derive(Clone)]
+pub struct ExtWorkspaces {
+    shared: Arc<Mutex<Shared>>,
+    conn: Connection,
+}
+
+impl ExtWorkspaces {
+    // Tell the bar a change has settled. The callback runs with the lock
+    // released, since it may well read the workspaces straight away.
+    fn done(&self) {
+        let Some(mut on_done) = self.shared.lock().unwrap().on_done.take() else {
+            return;
+        };
+        if on_done() {
+            self.shared.lock().unwrap().on_done.get_or_insert(on_done);
+        }
+    }
+
+    /// Bind the manager if the compositor advertises it
+    pub fn bind<D>(globals: &GlobalList, qh: &QueueHandle<D>, conn: &Connection) -> Option<Self>
+    where
+        D: Dispatch<ExtWorkspaceManagerV1, ()> + 'static,
+    {
+        let manager = globals.bind::<ExtWorkspaceManagerV1, D, ()>(qh, 1..=1, ()).ok()?;
+        let shared = Shared {
+            manager,
+            workspaces: Vec::new(),
+            groups: HashMap::new(),
+            output_names: HashMap::new(),
+            on_done: None,
+        };
+        Some(Self {
+            shared: Arc::new(Mutex::new(shared)),
+            conn: conn.clone(),
+        })
+    }
+
+    /// Name workspaces on `output` after it, e.g. "DP-1"
+    pub fn set_output_name(&self, output: &wl_output::WlOutput, name: Option<String>) {
+        let mut shared = self.shared.lock().unwrap();
+        match name {
+            Some(name) => shared.output_names.insert(output.id().protocol_id(), name),
+            None => shared.output_names.remove(&output.id().protocol_id()),
+        };
+    }
+
+    fn allows(&self, id: u64, capability: WorkspaceCapabilities) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .workspaces
+            .iter()
+            .any(|(key, entry)| u64::from(*key) == id && entry.capabilities.contains(capability))
+    }
+
+    // Send `request` to workspace `id` if the compositor allows it
+    fn request(
+        &self,
+        id: u64,
+        capability: WorkspaceCapabilities,
+        request: fn(&ExtWorkspaceHandleV1),
+    ) -> anyhow::Result<()> {
+        let shared = self.shared.lock().unwrap();
+        let Some((_, entry)) = shared.workspaces.iter().find(|(key, _)| u64::from(*key) == id) else {
+            bail!("no workspace with id {}", id);
+        };
+        if !entry.capabilities.contains(capability) {
+            bail!("the compositor doesn't allow that for workspace {}", id);
+        }
+        request(&entry.handle);
+        shared.manager.commit();
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+impl Compositor for ExtWorkspaces {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>> {
+        let shared = self.shared.lock().unwrap();
+        let infos: Vec<(u32, Info)> = shared
+            .workspaces
+            .iter()
+            .map(|(key, entry)| (*key, entry.info.clone()))
+            .collect();
+        let output = |group: u32| {
+            let outputs = &shared.groups.get(&group)?.outputs;
+            outputs.iter().find_map(|id| shared.output_names.get(id).cloned())
+        };
+        Ok(summarize(&infos, output))
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>> {
+        bail!("ext-workspace-v1 doesn't list windows")
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()> {
+        match action {
+            Action::FocusWorkspace { id } => {
+                self.request(id, WorkspaceCapabilities::Activate, ExtWorkspaceHandleV1::activate)
+            }
+            Action::DeactivateWorkspace { id } => {
+                self.request(id, WorkspaceCapabilities::Deactivate, ExtWorkspaceHandleV1::deactivate)
+            }
+            _ => bail!("ext-workspace-v1 only manages workspaces"),
+        }
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        match action {
+            Action::FocusWorkspace { id } => self.allows(*id, WorkspaceCapabilities::Activate),
+            Action::DeactivateWorkspace { id } => self.allows(*id, WorkspaceCapabilities::Deactivate),
+            _ => false,
+        }
+    }
+
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>) {
+        // Events are read with the rest of the Wayland connection; just ask
+        // to hear about each finished batch
+        self.shared.lock().unwrap().on_done = Some(on_event);
+    }
+}
+
+// Visible workspaces in the order the compositor announced them. The bar
+// shows coordinates when there are any and the position in the group if not.
+fn summarize(infos: &[(u32, Info)], output: impl Fn(u32) -> Option<String>) -> Vec<Workspace> {
+    let mut positions: HashMap<Option<u32>, u32> = HashMap::new();
+    infos
+        .iter()
+        .filter(|(_, info)| !info.hidden)
+        .map(|(key, info)| {
+            let position = positions.entry(info.group).or_default();
+            *position += 1;
+            let idx = info.coordinates.first().map_or(*position, |c| c + 1);
+
+            Workspace {
+                id: u64::from(*key),
+                idx,
+                name: info.name.clone(),
+                output: info.group.and_then(&output),
+                is_active: info.active,
+                // There's no notion of focus; each output's active workspace
+                // is the one its user is looking at
+                is_focused: info.active,
+                is_urgent: info.urgent,
+            }
+        })
+        .collect()
+}
+
+impl<D> Dispatch<ExtWorkspaceManagerV1, (), D> for ExtWorkspaces
+where
+    D: Dispatch<ExtWorkspaceManagerV1, ()>
+        + Dispatch<ExtWorkspaceGroupHandleV1, ()>
+        + Dispatch<ExtWorkspaceHandleV1, ()>
+        + ExtWorkspaceHandler
+        + 'static,
+{
+    fn event(
+        state: &mut D,
+        _manager: &ExtWorkspaceManagerV1,
+        event: ext_workspace_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(workspaces) = state.ext_workspaces() else {
+            return;
+        };
+        let mut shared = workspaces.shared.lock().unwrap();
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                shared.groups.insert(workspace_group.id().protocol_id(), Group::default());
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                let entry = Entry {
+                    handle: workspace.clone(),
+                    info: Info::default(),
+                    capabilities: WorkspaceCapabilities::empty(),
+                };
+                shared.workspaces.push((workspace.id().protocol_id(), entry));
+            }
+            ext_workspace_manager_v1::Event::Done => {
+                drop(shared);
+                workspaces.done();
+            }
+            ext_workspace_manager_v1::Event::Finished => {
+                shared.workspaces.clear();
+                shared.groups.clear();
+            }
+        }
+    }
+
+    event_created_child!(D, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ()),
+    ]);
+}
+
+impl<D> Dispatch<ExtWorkspaceGroupHandleV1, (), D> for ExtWorkspaces
+where
+    D: Dispatch<ExtWorkspaceGroupHandleV1, ()> + ExtWorkspaceHandler,
+{
+    fn event(
+        state: &mut D,
+        group: &ExtWorkspaceGroupHandleV1,
+        event: ext_workspace_group_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(workspaces) = state.ext_workspaces() else {
+            return;
+        };
+        let mut shared = workspaces.shared.lock().unwrap();
+        let key = group.id().protocol_id();
+        match event {
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                let outputs = &mut shared.groups.entry(key).or_default().outputs;
+                outputs.push(output.id().protocol_id());
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                if let Some(group) = shared.groups.get_mut(&key) {
+                    group.outputs.retain(|&id| id != output.id().protocol_id());
+                }
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                let id = workspace.id().protocol_id();
+                if let Some((_, entry)) = shared.workspaces.iter_mut().find(|(k, _)| *k == id) {
+                    entry.info.group = Some(key);
+                }
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                let id = workspace.id().protocol_id();
+                if let Some((_, entry)) = shared.workspaces.iter_mut().find(|(k, _)| *k == id) {
+                    if entry.info.group == Some(key) {
+                        entry.info.group = None;
+                    }
+                }
+            }
+            ext_workspace_group_handle_v1::Event::Removed => {
+                shared.groups.remove(&key);
+                group.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ExtWorkspaceHandleV1, (), D> for ExtWorkspaces
+where
+    D: Dispatch<ExtWorkspaceHandleV1, ()> + ExtWorkspaceHandler,
+{
+    fn event(
+        state: &mut D,
+        workspace: &ExtWorkspaceHandleV1,
+        event: ext_workspace_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(workspaces) = state.ext_workspaces() else {
+            return;
+        };
+        let mut shared = workspaces.shared.lock().unwrap();
+        let key = workspace.id().protocol_id();
+
+        if let ext_workspace_handle_v1::Event::Removed = event {
+            shared.workspaces.retain(|(k, _)| *k != key);
+            workspace.destroy();
+            return;
+        }
+        let Some((_, entry)) = shared.workspaces.iter_mut().find(|(k, _)| *k == key) else {
+            return;
+        };
+        match event {
+            ext_workspace_handle_v1::Event::Name { name } => entry.info.name = Some(name),
+            ext_workspace_handle_v1::Event::Coordinates { coordinates } => {
+                entry.info.coordinates = coordinates
+                    .chunks_exact(4)
+                    .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect();
+            }
+            ext_workspace_handle_v1::Event::State { state: WEnum::Value(state) } => {
+                use ext_workspace_handle_v1::State;
+                entry.info.active = state.contains(State::Active);
+                entry.info.urgent = state.contains(State::Urgent);
+                entry.info.hidden = state.contains(State::Hidden);
+            }
+            ext_workspace_handle_v1::Event::Capabilities { capabilities: WEnum::Value(capabilities) } => {
+                entry.capabilities = capabilities;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(group: u32, name: &str, active: bool) -> Info {
+        Info {
+            name: Some(name.to_string()),
+            active,
+            group: Some(group),
+            ..Info::default()
+        }
+    }
+
+    #[test]
+    fn workspaces_are_numbered_within_their_group() {
+        let infos = [
+            (10, info(1, "web", false)),
+            (11, info(1, "code", true)),
+            (12, info(2, "chat", true)),
+            (13, Info { hidden: true, ..info(2, "scratch", false) }),
+        ];
+        let outputs = |group| Some(if group == 1 { "DP-1" } else { "HDMI-A-1" }.to_string());
+
+        let workspaces = summarize(&infos, outputs);
+        let summary: Vec<_> = workspaces
+            .iter()
+            .map(|w| (w.id, w.idx, w.output.as_deref().unwrap(), w.is_focused))
+            .collect();
+        assert_eq!(
+            summary,
+            [(10, 1, "DP-1", false), (11, 2, "DP-1", true), (12, 1, "HDMI-A-1", true)]
+        );
+    }
+
+    #[test]
+    fn coordinates_win_over_position() {
+        let infos = [
+            (10, Info { coordinates: vec![4], ..info(1, "a", false) }),
+            (11, Info { coordinates: vec![0, 2], ..info(1, "b", false) }),
+            (12, Info { group: None, ..info(1, "c", false) }),
+        ];
+
+        let workspaces = summarize(&infos, |_| None);
+        let idx: Vec<_> = workspaces.iter().map(|w| (w.idx, w.output.clone())).collect();
+        assert_eq!(idx, [(5, None), (1, None), (1, None)]);
+    }
+}
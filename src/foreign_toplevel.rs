@@ -0,0 +1,295 @@
+// Windows over wlr-foreign-toplevel-management
+//
+// wlroots compositors (and labwc, Wayfire, river, ...) list their windows to
+// clients that bind zwlr_foreign_toplevel_manager_v1. As with ext-workspace,
+// events arrive on the bar's own event queue and the bar reads the latest
+// state whenever it draws.
+
+use anyhow::bail;
+use oxidebar::compositor::{Action, Compositor, Window, Workspace};
+use std::sync::{Arc, Mutex};
+use wayland_client::{
+    event_created_child, globals::GlobalList, protocol::wl_seat, Connection, Dispatch, Proxy,
+    QueueHandle,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/// Lets the protocol handlers find the shared state in the app
+pub trait ForeignToplevelHandler {
+    fn foreign_toplevels(&self) -> Option<&ForeignToplevels>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Info {
+    title: Option<String>,
+    app_id: Option<String>,
+    activated: bool,
+    minimized: bool,
+}
+
+struct Entry {
+    handle: ZwlrForeignToplevelHandleV1,
+    // What the last done event settled on; None until the first one
+    current: Option<Info>,
+    pending: Info,
+}
+
+struct Shared {
+    // Keyed by protocol id, which also serves as the window id
+    toplevels: Vec<(u32, Entry)>,
+    // Activating a window needs a seat to activate it on
+    seat: Option<wl_seat::WlSeat>,
+    on_done: Option<Box<dyn FnMut() -> bool + Send>>,
+}
+
+/// The windows the compositor last described; clones share the state
+#[derive(Clone)]
+pub struct ForeignToplevels {
+    shared: Arc<Mutex<Shared>>,
+    conn: Connection,
+}
+
+impl ForeignToplevels {
+    // Tell the bar a change has settled. The callback runs with the lock
+    // released, since it may well read the windows straight away.
+    fn done(&self) {
+        let Some(mut on_done) = self.shared.lock().unwrap().on_done.take() else {
+            return;
+        };
+        if on_done() {
+            self.shared.lock().unwrap().on_done.get_or_insert(on_done);
+        }
+    }
+
+    /// Bind the manager if the compositor advertises it
+    pub fn bind<D>(globals: &GlobalList, qh: &QueueHandle<D>, conn: &Connection) -> Option<Self>
+    where
+        D: Dispatch<ZwlrForeignToplevelManagerV1, ()> + 'static,
+    {
+        globals.bind::<ZwlrForeignToplevelManagerV1, D, ()>(qh, 1..=3, ()).ok()?;
+        let shared = Shared {
+            toplevels: Vec::new(),
+            seat: None,
+            on_done: None,
+        };
+        Some(Self {
+            shared: Arc::new(Mutex::new(shared)),
+            conn: conn.clone(),
+        })
+    }
+
+    /// Activate windows on `seat` from now on
+    pub fn set_seat(&self, seat: &wl_seat::WlSeat) {
+        self.shared.lock().unwrap().seat = Some(seat.clone());
+    }
+
+    // Send `request` to window `id`
+    fn request(
+        &self,
+        id: u64,
+        request: impl FnOnce(&Entry, &Shared) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let shared = self.shared.lock().unwrap();
+        let Some((_, entry)) = shared.toplevels.iter().find(|(key, _)| u64::from(*key) == id) else {
+            bail!("no window with id {}", id);
+        };
+        request(entry, &shared)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+impl Compositor for ForeignToplevels {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>> {
+        bail!("wlr-foreign-toplevel-management doesn't list workspaces")
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>> {
+        let shared = self.shared.lock().unwrap();
+        Ok(shared
+            .toplevels
+            .iter()
+            .filter_map(|(key, entry)| Some(window(*key, entry.current.as_ref()?)))
+            .collect())
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()> {
+        match action {
+            Action::FocusWindow { id } => self.request(id, |entry, shared| {
+                let Some(seat) = &shared.seat else {
+                    bail!("no seat to activate window {} on", id);
+                };
+                entry.handle.activate(seat);
+                Ok(())
+            }),
+            // Minimizing a minimized window brings it back
+            Action::MinimizeWindow { id } => self.request(id, |entry, _| {
+                if entry.current.as_ref().is_some_and(|info| info.minimized) {
+                    entry.handle.unset_minimized();
+                } else {
+                    entry.handle.set_minimized();
+                }
+                Ok(())
+            }),
+            Action::CloseWindow { id } => self.request(id, |entry, _| {
+                entry.handle.close();
+                Ok(())
+            }),
+            _ => bail!("wlr-foreign-toplevel-management only manages windows"),
+        }
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        matches!(
+            action,
+            Action::FocusWindow { .. } | Action::MinimizeWindow { .. } | Action::CloseWindow { .. }
+        )
+    }
+
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>) {
+        // Events are read with the rest of the Wayland connection; just ask
+        // to hear about each finished batch
+        self.shared.lock().unwrap().on_done = Some(on_event);
+    }
+}
+
+// There's no notion of workspaces here, so windows belong to none
+fn window(key: u32, info: &Info) -> Window {
+    Window {
+        id: u64::from(key),
+        title: info.title.clone(),
+        app_id: info.app_id.clone(),
+        workspace_id: None,
+        is_focused: info.activated,
+    }
+}
+
+// The state event carries an array of native-endian u32 states
+fn apply_states(info: &mut Info, states: &[u8]) {
+    use zwlr_foreign_toplevel_handle_v1::State;
+
+    let states: Vec<u32> = states
+        .chunks_exact(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    info.activated = states.contains(&(State::Activated as u32));
+    info.minimized = states.contains(&(State::Minimized as u32));
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevels
+where
+    D: Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ()>
+        + ForeignToplevelHandler
+        + 'static,
+{
+    fn event(
+        state: &mut D,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(toplevels) = state.foreign_toplevels() else {
+            return;
+        };
+        let mut shared = toplevels.shared.lock().unwrap();
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                let entry = Entry {
+                    handle: toplevel.clone(),
+                    current: None,
+                    pending: Info::default(),
+                };
+                shared.toplevels.push((toplevel.id().protocol_id(), entry));
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                shared.toplevels.clear();
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(D, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelHandleV1, (), D> for ForeignToplevels
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ()> + ForeignToplevelHandler,
+{
+    fn event(
+        state: &mut D,
+        toplevel: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(toplevels) = state.foreign_toplevels() else {
+            return;
+        };
+        let mut guard = toplevels.shared.lock().unwrap();
+        let shared = &mut *guard;
+        let key = toplevel.id().protocol_id();
+
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            shared.toplevels.retain(|(k, _)| *k != key);
+            toplevel.destroy();
+            drop(guard);
+            toplevels.done();
+            return;
+        }
+        let Some((_, entry)) = shared.toplevels.iter_mut().find(|(k, _)| *k == key) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => entry.pending.title = Some(title),
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => entry.pending.app_id = Some(app_id),
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => apply_states(&mut entry.pending, &state),
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                entry.current = Some(entry.pending.clone());
+                drop(guard);
+                toplevels.done();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn states(states: &[u32]) -> Vec<u8> {
+        states.iter().flat_map(|s| s.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn states_replace_the_previous_ones() {
+        let mut info = Info::default();
+        // maximized, activated
+        apply_states(&mut info, &states(&[0, 2]));
+        assert!(info.activated && !info.minimized);
+
+        apply_states(&mut info, &states(&[1]));
+        assert!(!info.activated && info.minimized);
+    }
+
+    #[test]
+    fn windows_belong_to_no_workspace() {
+        let info = Info {
+            title: Some("vim".to_string()),
+            app_id: Some("foot".to_string()),
+            activated: true,
+            ..Info::default()
+        };
+        let window = window(7, &info);
+        assert_eq!((window.id, window.workspace_id, window.is_focused), (7, None, true));
+    }
+}
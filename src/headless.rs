@@ -0,0 +1,70 @@
+// Headless rendering: draw the bar into memory without a compositor
+//
+// Used by `oxidebar render` and the snapshot tests. Modules read fixed sources
+// rather than the machine, and their content can be pinned with overrides, so
+// the output doesn't depend on where or when it's rendered.
+
+use crate::bar::BarRenderer;
+use crate::config::Config;
+use crate::modules::ModuleOutput;
+use crate::sources::Sources;
+use std::collections::HashMap;
+
+/// A rendered bar in premultiplied ARGB8888, native byte order
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Straight-alpha RGBA bytes, as image formats expect
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.data.len());
+        for pixel in self.data.chunks_exact(4) {
+            let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            let a = argb >> 24;
+            let unpremultiply = |c: u32| match a {
+                0 => 0,
+                _ => ((c * 255 + a / 2) / a).min(255) as u8,
+            };
+            rgba.extend_from_slice(&[
+                unpremultiply((argb >> 16) & 0xFF),
+                unpremultiply((argb >> 8) & 0xFF),
+                unpremultiply(argb & 0xFF),
+                a as u8,
+            ]);
+        }
+        rgba
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.to_rgba())?;
+        Ok(out)
+    }
+}
+
+/// Render one frame of the bar. Modules without an override read
+/// [`Sources::fixed`], so most show nothing and the clock shows noon.
+pub fn render(
+    config: Config,
+    width: u32,
+    height: u32,
+    overrides: HashMap<String, ModuleOutput>,
+) -> Image {
+    let mut renderer = BarRenderer::with_sources(config, Sources::fixed());
+    for (name, output) in overrides {
+        renderer.set_override(&name, output);
+    }
+    renderer.render(width, height);
+
+    Image {
+        width,
+        height,
+        data: renderer.frame().to_vec(),
+    }
+}
@@ -0,0 +1,276 @@
+// Hyprland's IPC, over the two sockets in its instance directory
+//
+// `.socket.sock` takes one request per connection, e.g. `j/workspaces` for
+// JSON or `dispatch workspace 2`, and closes after replying. `.socket2.sock`
+// streams events as `EVENT>>DATA` lines.
+
+use crate::compositor::{self, Action, Compositor, KeyboardLayouts, Window, Workspace};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// How long Hyprland may take to answer before it counts as unreachable
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+struct HyprWorkspace {
+    id: i64,
+    name: String,
+    monitor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Monitor {
+    #[serde(rename = "activeWorkspace")]
+    active_workspace: WorkspaceRef,
+    focused: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceRef {
+    id: i64,
+}
+
+// A window, as `clients` and `activewindow` report it
+#[derive(Debug, Deserialize)]
+struct Client {
+    address: String,
+    #[serde(default = "default_true")]
+    mapped: bool,
+    workspace: WorkspaceRef,
+    class: String,
+    title: String,
+    // 0 for the focused window, counting up from there
+    #[serde(rename = "focusHistoryID")]
+    focus_history_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Devices {
+    keyboards: Vec<Keyboard>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Keyboard {
+    // Every configured layout by its xkb name, comma-separated, e.g. "us,de"
+    #[serde(default)]
+    layout: String,
+    active_keymap: String,
+    // Index into `layout`; missing from older releases
+    active_layout_index: Option<usize>,
+    #[serde(default)]
+    main: bool,
+}
+
+pub struct HyprlandIpc {
+    // Holds .socket.sock and .socket2.sock
+    dir: PathBuf,
+}
+
+impl HyprlandIpc {
+    /// Connect to the Hyprland instance named by HYPRLAND_INSTANCE_SIGNATURE,
+    /// if any
+    pub fn new() -> Option<Self> {
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+
+        // Hyprland 0.40 moved its sockets from /tmp into the runtime dir
+        let runtime = env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr").join(&signature))
+            .filter(|dir| dir.exists());
+        let dir = runtime.unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&signature));
+        Some(Self::with_dir(dir))
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Send one request and return the reply, e.g. `dispatch workspace 2`
+    pub fn request(&self, request: &str) -> anyhow::Result<String> {
+        let mut stream =
+            UnixStream::connect(self.dir.join(".socket.sock")).context("can't reach Hyprland")?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.write_all(request.as_bytes())?;
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    }
+
+    // A `j/` request, decoded as `T`
+    fn query<T: for<'de> Deserialize<'de>>(&self, what: &str) -> anyhow::Result<T> {
+        let reply = self.request(&format!("j/{}", what))?;
+        serde_json::from_str(&reply).with_context(|| format!("unexpected reply to {}: {:?}", what, reply))
+    }
+
+    // Commands answer "ok" or say what went wrong
+    fn command(&self, command: &str) -> anyhow::Result<()> {
+        let reply = self.request(command)?;
+        if reply.trim() != "ok" {
+            bail!("Hyprland: {}", reply.trim());
+        }
+        Ok(())
+    }
+
+    // `activewindow` replies with an empty object when nothing is focused
+    fn active_window(&self) -> anyhow::Result<Option<Client>> {
+        let reply: serde_json::Value = self.query("activewindow")?;
+        if reply.as_object().is_some_and(|o| o.is_empty()) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_value(reply).context("unexpected reply to activewindow")?))
+    }
+
+    // Read events until the connection drops; false once `on_event` asks to stop
+    fn follow_events(&self, on_event: &mut dyn FnMut() -> bool) -> bool {
+        let Ok(stream) = UnixStream::connect(self.dir.join(".socket2.sock")) else {
+            return true;
+        };
+        for line in BufReader::new(stream).lines() {
+            if line.is_err() {
+                return true;
+            }
+            if !on_event() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Compositor for HyprlandIpc {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>> {
+        let workspaces: Vec<HyprWorkspace> = self.query("workspaces")?;
+        let monitors: Vec<Monitor> = self.query("monitors")?;
+        let is_active = |id| monitors.iter().any(|m| m.active_workspace.id == id);
+        let focused = monitors.iter().find(|m| m.focused).map(|m| m.active_workspace.id);
+
+        Ok(workspaces
+            .into_iter()
+            // Special workspaces (scratchpads) have negative ids
+            .filter(|ws| ws.id > 0)
+            .map(|ws| Workspace {
+                id: ws.id as u64,
+                idx: ws.id as u32,
+                name: Some(ws.name),
+                output: Some(ws.monitor),
+                is_active: is_active(ws.id),
+                is_focused: focused == Some(ws.id),
+                is_urgent: false,
+            })
+            .collect())
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>> {
+        let clients: Vec<Client> = self.query("clients")?;
+        Ok(clients
+            .into_iter()
+            .filter(|c| c.mapped)
+            .map(|c| {
+                let is_focused = c.focus_history_id == Some(0);
+                window(c, is_focused)
+            })
+            .collect())
+    }
+
+    fn focused_window(&self) -> anyhow::Result<Option<Window>> {
+        Ok(self.active_window()?.map(|c| window(c, true)))
+    }
+
+    fn keyboard_layouts(&self) -> anyhow::Result<KeyboardLayouts> {
+        let devices: Devices = self.query("devices")?;
+        let keyboard = devices
+            .keyboards
+            .iter()
+            .find(|k| k.main)
+            .or(devices.keyboards.first());
+        let Some(keyboard) = keyboard else {
+            bail!("Hyprland reports no keyboards");
+        };
+        let names: Vec<String> = keyboard.layout
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        match keyboard.active_layout_index {
+            Some(current_idx) if current_idx < names.len() => Ok(KeyboardLayouts { names, current_idx }),
+            // Without the index only the active layout can be named
+            _ => Ok(KeyboardLayouts {
+                names: vec![keyboard.active_keymap.clone()],
+                current_idx: 0,
+            }),
+        }
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()> {
+        let command = match action {
+            Action::FocusWorkspace { id } => format!("dispatch workspace {}", id),
+            Action::DeactivateWorkspace { .. } => bail!("Hyprland can't deactivate workspaces"),
+            Action::FocusWindow { id } => format!("dispatch focuswindow address:0x{:x}", id),
+            Action::MinimizeWindow { .. } => bail!("Hyprland can't minimize windows"),
+            Action::CloseWindow { id } => format!("dispatch closewindow address:0x{:x}", id),
+            Action::NextLayout => String::from("switchxkblayout all next"),
+            Action::PrevLayout => String::from("switchxkblayout all prev"),
+        };
+        self.command(&command)
+    }
+
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>) {
+        let hyprland = Self::with_dir(self.dir.clone());
+        // Whatever the bar shows may be stale after a disconnect
+        compositor::spawn_reconnecting(
+            move |on_event| hyprland.follow_events(on_event),
+            on_event,
+            |on_event| on_event(),
+        );
+    }
+}
+
+fn window(client: Client, is_focused: bool) -> Window {
+    Window {
+        id: parse_address(&client.address).unwrap_or(0),
+        title: Some(client.title),
+        app_id: Some(client.class),
+        workspace_id: u64::try_from(client.workspace.id).ok().filter(|&id| id > 0),
+        is_focused,
+    }
+}
+
+// Windows are named by their address, "0x55d0c4a1b2c0"
+fn parse_address(address: &str) -> Option<u64> {
+    u64::from_str_radix(address.trim_start_matches("0x"), 16).ok()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_hex() {
+        assert_eq!(parse_address("0x55d0c4a1b2c0"), Some(0x55d0c4a1b2c0));
+        assert_eq!(parse_address("ff"), Some(255));
+        assert_eq!(parse_address("0xnope"), None);
+    }
+
+    #[test]
+    fn special_workspaces_hold_no_windows() {
+        let client: Client = serde_json::from_str(
+            r#"{"address": "0x10", "mapped": true, "hidden": true,
+                "workspace": {"id": -98, "name": "special:scratch"},
+                "class": "kitty", "title": "scratch"}"#,
+        )
+        .unwrap();
+        let window = window(client, false);
+        assert_eq!((window.id, window.workspace_id), (16, None));
+    }
+}
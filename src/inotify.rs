@@ -0,0 +1,75 @@
+// File change notifications over inotify(7)
+//
+// Used where a module would otherwise have to poll a file, e.g. backlight
+// brightness, which sysfs reports as modified whenever it's written.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// Call `on_change` from a background thread whenever any of `paths` is
+/// written, until it returns false
+pub fn watch(paths: &[PathBuf], mut on_change: Box<dyn FnMut() -> bool + Send>) -> io::Result<()> {
+    if paths.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no files to watch"));
+    }
+    let paths = paths
+        .iter()
+        .map(|path| CString::new(path.as_os_str().as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    // SAFETY: plain syscalls on a descriptor this function owns
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    for path in &paths {
+        if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), libc::IN_MODIFY) } < 0 {
+            let error = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(error);
+        }
+    }
+
+    std::thread::spawn(move || {
+        // Room for a batch of events; only their arrival matters
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            if read <= 0 || !on_change() {
+                break;
+            }
+        }
+        unsafe { libc::close(fd) };
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn writes_are_reported() {
+        let dir = Fixture::new();
+        dir.write("brightness", "100\n").write("actual_brightness", "100\n");
+        let files = [dir.path().join("brightness"), dir.path().join("actual_brightness")];
+        let (sender, changes) = mpsc::channel();
+        watch(&files, Box::new(move || sender.send(()).is_ok())).unwrap();
+
+        dir.write("brightness", "50\n");
+        assert!(changes.recv_timeout(Duration::from_secs(1)).is_ok());
+        while changes.recv_timeout(Duration::from_millis(100)).is_ok() {}
+        dir.write("actual_brightness", "50\n");
+        assert!(changes.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        assert!(watch(&[dir.path().join("missing")], Box::new(|| true)).is_err());
+        assert!(watch(&[], Box::new(|| true)).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+// Bar layout: turns measured modules into rectangles
+//
+// Pure geometry with no knowledge of rendering, so the result can be reused
+// for drawing, hit-testing and damage tracking, and tested without a canvas.
+
+use crate::canvas::Rect;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Left,
+    Center,
+    Right,
+}
+
+impl Region {
+    fn index(self) -> usize {
+        match self {
+            Region::Left => 0,
+            Region::Center => 1,
+            Region::Right => 2,
+        }
+    }
+}
+
+/// Where a module's content sits when its box is wider than the content
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Start,
+    #[default]
+    Center,
+    End,
+}
+
+/// A measured module waiting to be placed
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub region: Region,
+    /// Natural width of the content
+    pub content_width: i32,
+    /// The box never gets narrower than this, even when regions collide
+    pub min_width: i32,
+    pub max_width: Option<i32>,
+    /// Share of leftover space the box grows by; 0 keeps its natural width
+    pub flex: u32,
+    /// When regions collide, lower priorities shrink first and are hidden first
+    pub priority: i32,
+    pub align: Align,
+}
+
+impl Item {
+    pub fn new(region: Region, content_width: i32) -> Self {
+        Self {
+            region,
+            content_width,
+            min_width: 0,
+            max_width: None,
+            flex: 0,
+            priority: 0,
+            align: Align::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    pub width: i32,
+    pub height: i32,
+    /// Space between the bar edges and the outermost modules
+    pub padding: i32,
+    /// Space between neighbouring modules of a region
+    pub gap: i32,
+}
+
+/// Where an item ended up; `None` for items hidden to make room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// The module's full box
+    pub rect: Rect,
+    /// The part of the box the content occupies after alignment
+    pub content: Rect,
+}
+
+/// Lay out `items`, returning one entry per item in the same order
+pub fn layout(items: &[Item], params: &Params) -> Vec<Option<Placement>> {
+    let mut widths: Vec<i32> = items
+        .iter()
+        .map(|item| {
+            let width = item.content_width.max(item.min_width);
+            item.max_width.map_or(width, |max| width.min(max.max(item.min_width)))
+        })
+        .collect();
+    let mut visible = vec![true; items.len()];
+
+    resolve_collisions(items, params, &mut widths, &mut visible);
+    grow_flex(items, params, &mut widths, &visible);
+
+    // Left flows from the left edge, center is centered as a group and right
+    // is right-aligned
+    let extents = Extents::new(items, params, &widths, &visible);
+    let mut x = [extents.left_start(params), extents.center_start(params), extents.right_start(params)];
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            if !visible[i] {
+                return None;
+            }
+            let region = item.region.index();
+            let rect = Rect::new(x[region], 0, widths[i], params.height);
+            x[region] += widths[i] + params.gap;
+
+            let content_width = item.content_width.min(rect.width);
+            let offset = match item.align {
+                Align::Start => 0,
+                Align::Center => (rect.width - content_width) / 2,
+                Align::End => rect.width - content_width,
+            };
+            let content = Rect::new(rect.x + offset, rect.y, content_width, rect.height);
+            Some(Placement { rect, content })
+        })
+        .collect()
+}
+
+// Total widths of the three regions
+struct Extents {
+    widths: [i32; 3],
+    counts: [usize; 3],
+}
+
+impl Extents {
+    fn new(items: &[Item], params: &Params, widths: &[i32], visible: &[bool]) -> Self {
+        let mut extents = Extents { widths: [0; 3], counts: [0; 3] };
+        for (i, item) in items.iter().enumerate() {
+            if !visible[i] {
+                continue;
+            }
+            let region = item.region.index();
+            if extents.counts[region] > 0 {
+                extents.widths[region] += params.gap;
+            }
+            extents.widths[region] += widths[i];
+            extents.counts[region] += 1;
+        }
+        extents
+    }
+
+    fn is_empty(&self, region: Region) -> bool {
+        self.counts[region.index()] == 0
+    }
+
+    fn left_start(&self, params: &Params) -> i32 {
+        params.padding
+    }
+
+    fn left_end(&self, params: &Params) -> i32 {
+        params.padding + self.widths[0]
+    }
+
+    fn center_start(&self, params: &Params) -> i32 {
+        (params.width - self.widths[1]) / 2
+    }
+
+    fn center_end(&self, params: &Params) -> i32 {
+        self.center_start(params) + self.widths[1]
+    }
+
+    fn right_start(&self, params: &Params) -> i32 {
+        params.width - params.padding - self.widths[2]
+    }
+
+    // Space between two regions, or a negative overlap; the gap only
+    // applies when both sides have modules
+    fn space_between(&self, params: &Params, a: Region, b: Region, a_end: i32, b_start: i32) -> i32 {
+        let gap = if self.is_empty(a) || self.is_empty(b) { 0 } else { params.gap };
+        b_start - a_end - gap
+    }
+
+    // Free space between left and center, and between center and right.
+    // Without center modules both describe the same stretch.
+    fn free_space(&self, params: &Params) -> (i32, i32) {
+        if self.is_empty(Region::Center) {
+            let free = self.space_between(
+                params,
+                Region::Left,
+                Region::Right,
+                self.left_end(params),
+                self.right_start(params),
+            );
+            return (free, free);
+        }
+        (
+            self.space_between(
+                params,
+                Region::Left,
+                Region::Center,
+                self.left_end(params),
+                self.center_start(params),
+            ),
+            self.space_between(
+                params,
+                Region::Center,
+                Region::Right,
+                self.center_end(params),
+                self.right_start(params),
+            ),
+        )
+    }
+}
+
+// Shrink, then hide, the lowest priority modules involved in an overlap
+// until the regions fit next to each other
+fn resolve_collisions(items: &[Item], params: &Params, widths: &mut [i32], visible: &mut [bool]) {
+    loop {
+        let extents = Extents::new(items, params, widths, visible);
+        let (left_free, right_free) = extents.free_space(params);
+
+        let (overlap, involved) = if extents.is_empty(Region::Center) {
+            (-left_free, [true, false, true])
+        } else if left_free <= right_free {
+            (-left_free, [true, true, false])
+        } else {
+            (-right_free, [false, true, true])
+        };
+        if overlap <= 0 {
+            return;
+        }
+
+        let candidates = || {
+            (0..items.len()).filter(|&i| visible[i] && involved[items[i].region.index()])
+        };
+
+        let shrinkable = candidates()
+            .filter(|&i| widths[i] > items[i].min_width)
+            .min_by_key(|&i| (items[i].priority, -widths[i]));
+        if let Some(i) = shrinkable {
+            // Shrinking a centered module only frees half its width on each side
+            let factor = if items[i].region == Region::Center { 2 } else { 1 };
+            widths[i] = (widths[i] - overlap * factor).max(items[i].min_width);
+            continue;
+        }
+
+        // Everything is at its minimum; drop the least important module,
+        // preferring the later one on ties
+        let hidden = candidates()
+            .rev()
+            .min_by_key(|&i| items[i].priority);
+        match hidden {
+            Some(i) => visible[i] = false,
+            None => return,
+        }
+    }
+}
+
+// Hand leftover space to flexible modules: center modules grow evenly on both
+// sides, left and right modules take the free space next to them
+fn grow_flex(items: &[Item], params: &Params, widths: &mut [i32], visible: &[bool]) {
+    let flexible = |region: Region| -> Vec<usize> {
+        (0..items.len())
+            .filter(|&i| visible[i] && items[i].region == region && items[i].flex > 0)
+            .collect()
+    };
+
+    let extents = Extents::new(items, params, widths, visible);
+    if !extents.is_empty(Region::Center) {
+        let (left_free, right_free) = extents.free_space(params);
+        let grow = 2 * left_free.min(right_free).max(0);
+        distribute(items, widths, &flexible(Region::Center), grow);
+    }
+
+    let extents = Extents::new(items, params, widths, visible);
+    let (left_free, right_free) = extents.free_space(params);
+    if extents.is_empty(Region::Center) {
+        // Left and right share the stretch between them
+        let mut sides = flexible(Region::Left);
+        sides.extend(flexible(Region::Right));
+        distribute(items, widths, &sides, left_free.max(0));
+    } else {
+        distribute(items, widths, &flexible(Region::Left), left_free.max(0));
+        distribute(items, widths, &flexible(Region::Right), right_free.max(0));
+    }
+}
+
+// Split `space` between `indices` in proportion to their flex, respecting
+// max widths; any remainder stays unused
+fn distribute(items: &[Item], widths: &mut [i32], indices: &[usize], space: i32) {
+    let total_flex: u32 = indices.iter().map(|&i| items[i].flex).sum();
+    if total_flex == 0 || space <= 0 {
+        return;
+    }
+    for &i in indices {
+        let share = (space as i64 * items[i].flex as i64 / total_flex as i64) as i32;
+        let grown = widths[i] + share;
+        widths[i] = items[i].max_width.map_or(grown, |max| grown.min(max.max(widths[i])));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(width: i32) -> Params {
+        Params { width, height: 30, padding: 10, gap: 5 }
+    }
+
+    fn rects(placements: &[Option<Placement>]) -> Vec<Option<(i32, i32)>> {
+        placements.iter().map(|p| p.map(|p| (p.rect.x, p.rect.width))).collect()
+    }
+
+    #[test]
+    fn regions_are_placed_left_center_and_right() {
+        let items = vec![
+            Item::new(Region::Left, 40),
+            Item::new(Region::Left, 20),
+            Item::new(Region::Center, 30),
+            Item::new(Region::Right, 50),
+        ];
+        let placed = layout(&items, &params(400));
+
+        assert_eq!(
+            rects(&placed),
+            vec![Some((10, 40)), Some((55, 20)), Some((185, 30)), Some((340, 50))]
+        );
+    }
+
+    #[test]
+    fn max_and_min_width_clamp_the_box() {
+        let mut wide = Item::new(Region::Left, 300);
+        wide.max_width = Some(100);
+        let mut narrow = Item::new(Region::Left, 10);
+        narrow.min_width = 40;
+        narrow.align = Align::End;
+
+        let placed = layout(&[wide, narrow], &params(400));
+        let narrow = placed[1].unwrap();
+
+        assert_eq!(placed[0].unwrap().rect.width, 100);
+        assert_eq!(placed[0].unwrap().content.width, 100);
+        assert_eq!(narrow.rect.width, 40);
+        assert_eq!(narrow.content, Rect::new(narrow.rect.x + 30, 0, 10, 30));
+    }
+
+    #[test]
+    fn content_is_aligned_within_the_box() {
+        let align = |align: Align| {
+            let mut item = Item::new(Region::Left, 20);
+            item.min_width = 60;
+            item.align = align;
+            layout(&[item], &params(400))[0].unwrap().content.x
+        };
+
+        assert_eq!(align(Align::Start), 10);
+        assert_eq!(align(Align::Center), 30);
+        assert_eq!(align(Align::End), 50);
+    }
+
+    #[test]
+    fn flex_items_share_the_free_space() {
+        let mut one = Item::new(Region::Left, 20);
+        one.flex = 1;
+        let mut three = Item::new(Region::Right, 20);
+        three.flex = 3;
+
+        // 400 - 2 * 10 padding - 2 * 20 content - 5 gap = 335 free
+        let placed = layout(&[one, three], &params(400));
+
+        assert_eq!(placed[0].unwrap().rect.width, 20 + 83);
+        assert_eq!(placed[1].unwrap().rect.width, 20 + 251);
+        assert!(placed[0].unwrap().rect.right() + 5 <= placed[1].unwrap().rect.x);
+    }
+
+    #[test]
+    fn centered_flex_stays_centered() {
+        let left = Item::new(Region::Left, 100);
+        let mut center = Item::new(Region::Center, 20);
+        center.flex = 1;
+        center.max_width = Some(1000);
+
+        let placed = layout(&[left, center], &params(400));
+        let center = placed[1].unwrap().rect;
+
+        assert_eq!(center.x + center.width / 2, 200);
+        assert!(placed[0].unwrap().rect.right() + 5 <= center.x);
+    }
+
+    #[test]
+    fn collisions_shrink_lowest_priority_first() {
+        let mut important = Item::new(Region::Left, 150);
+        important.priority = 10;
+        important.min_width = 20;
+        let mut unimportant = Item::new(Region::Right, 150);
+        unimportant.min_width = 20;
+
+        // 300 of content and a gap in 200 - 20 of usable space
+        let placed = layout(&[important, unimportant], &params(200));
+
+        assert_eq!(placed[0].unwrap().rect.width, 150);
+        assert_eq!(placed[1].unwrap().rect.width, 25);
+        assert_eq!(placed[1].unwrap().content.width, 25);
+    }
+
+    #[test]
+    fn collisions_with_center_shrink_twice_as_much() {
+        let left = Item::new(Region::Left, 100);
+        let mut center = Item::new(Region::Center, 100);
+        center.priority = -1;
+
+        // Center starts at 125, left ends at 110 + gap: no overlap yet
+        let placed = layout(&[left.clone(), center.clone()], &params(350));
+        assert_eq!(placed[1].unwrap().rect.width, 100);
+
+        // Center would start at 100; 15 px overlap costs it 30 px
+        let placed = layout(&[left, center], &params(300));
+        let center = placed[1].unwrap().rect;
+        assert_eq!(center.width, 70);
+        assert_eq!(placed[0].unwrap().rect.right() + 5, center.x);
+    }
+
+    #[test]
+    fn narrow_outputs_hide_lowest_priority_modules() {
+        let mut keep = Item::new(Region::Left, 100);
+        keep.min_width = 100;
+        keep.priority = 1;
+        let mut drop = Item::new(Region::Right, 100);
+        drop.min_width = 100;
+
+        let placed = layout(&[keep, drop], &params(150));
+
+        assert!(placed[0].is_some());
+        assert!(placed[1].is_none());
+    }
+}
@@ -0,0 +1,18 @@
+// oxidebar as a library: the renderer and modules without the Wayland client,
+// so the bar can be drawn headless and tested
+
+pub mod bar;
+pub mod canvas;
+pub mod compositor;
+pub mod config;
+pub mod headless;
+pub mod hyprland;
+pub mod inotify;
+pub mod layout;
+pub mod modules;
+pub mod niri;
+pub mod sources;
+pub mod sway;
+
+#[cfg(test)]
+mod testing;
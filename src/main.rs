@@ -1,34 +1,61 @@
 use anyhow::Result;
 use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_seat,
-    delegate_shm,
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_layer, delegate_output, delegate_pointer, delegate_registry,
+    delegate_seat, delegate_shm,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
-    seat::{Capability, SeatHandler, SeatState},
+    seat::{
+        pointer::{AxisScroll, PointerEvent, PointerEventKind, PointerHandler, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT},
+        Capability, SeatHandler, SeatState,
+    },
     shell::{
         wlr_layer::{
             Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
             LayerSurfaceConfigure,
         },
     },
-    shm::{slot::SlotPool, Shm, ShmHandler},
+    shm::{Shm, ShmHandler},
+};
+use smithay_client_toolkit::reexports::{
+    calloop::{channel, timer::{TimeoutAction, Timer}, EventLoop},
+    calloop_wayland_source::WaylandSource,
 };
+use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1};
+use signal_hook::iterator::Signals;
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
 use wayland_client::{
+    delegate_dispatch,
     globals::registry_queue_init,
-    protocol::{wl_output, wl_seat, wl_surface},
+    protocol::{wl_output, wl_pointer, wl_seat, wl_surface},
     Connection, QueueHandle,
 };
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1,
+    zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
+};
 
-mod bar;
-mod modules;
-mod niri;
-mod config;
-use bar::BarRenderer;
-use config::Config;
+mod ext_workspace;
+mod foreign_toplevel;
+mod protocol_compositor;
+mod swapchain;
+use oxidebar::bar::{BarRenderer, Button, TEXT_HEIGHT};
+use oxidebar::canvas::Rect;
+use oxidebar::config::Config;
+use oxidebar::headless;
+use oxidebar::modules::{ModuleOutput, ModuleState};
+use oxidebar::compositor::Compositor;
+use oxidebar::sources::Sources;
+use ext_workspace::{ExtWorkspaceHandler, ExtWorkspaces};
+use foreign_toplevel::{ForeignToplevelHandler, ForeignToplevels};
+use protocol_compositor::ProtocolCompositor;
+use swapchain::Swapchain;
+
+// Pixels of smooth scrolling that count as one wheel notch
+const SCROLL_NOTCH: f64 = 15.0;
 
 // Main application state
 struct WaybarTui {
@@ -42,23 +69,37 @@ struct WaybarTui {
     // Our bar surface
     layer_surface: Option<LayerSurface>,
     surface: Option<wl_surface::WlSurface>,
+    pointer: Option<wl_pointer::WlPointer>,
+    // Smooth scrolling not yet amounting to a whole notch
+    scroll_remainder: f64,
     
     // Rendering
-    pool: Option<SlotPool>,
+    swapchain: Option<Swapchain>,
     width: u32,
     height: u32,
+    // Repainted regions not yet sent to the compositor
+    pending_damage: Vec<Rect>,
     
     // Bar renderer
     renderer: Arc<Mutex<BarRenderer>>,
+    // Workspaces from ext-workspace-v1, when no compositor IPC is available
+    ext_workspaces: Option<ExtWorkspaces>,
+    // Windows from wlr-foreign-toplevel-management, likewise
+    toplevels: Option<ForeignToplevels>,
     
     // Configuration
     configured: bool,
     
-    // Update tracking
-    last_update: Instant,
+    // A frame callback is outstanding; hold off drawing until it fires
+    frame_pending: bool,
+    // Something changed while a frame was pending
+    needs_redraw: bool,
+    
+    exit: bool,
 }
 
 impl WaybarTui {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         registry_state: RegistryState,
         seat_state: SeatState,
@@ -66,7 +107,9 @@ impl WaybarTui {
         compositor_state: CompositorState,
         shm_state: Shm,
         layer_shell: LayerShell,
-        config: Config,
+        renderer: BarRenderer,
+        ext_workspaces: Option<ExtWorkspaces>,
+        toplevels: Option<ForeignToplevels>,
         bar_height: u32,
     ) -> Self {
         Self {
@@ -78,12 +121,44 @@ impl WaybarTui {
             layer_shell,
             layer_surface: None,
             surface: None,
-            pool: None,
+            pointer: None,
+            scroll_remainder: 0.0,
+            swapchain: None,
             width: 0,
             height: bar_height,
-            renderer: Arc::new(Mutex::new(BarRenderer::new(config))),
+            pending_damage: Vec::new(),
+            renderer: Arc::new(Mutex::new(renderer)),
+            ext_workspaces,
+            toplevels,
             configured: false,
-            last_update: Instant::now(),
+            frame_pending: false,
+            needs_redraw: false,
+            exit: false,
+        }
+    }
+
+    // Whole notches in a vertical scroll; wheels report them, touchpads
+    // only distances, which add up until they make one
+    fn scroll_steps(&mut self, scroll: AxisScroll) -> i32 {
+        if scroll.discrete != 0 {
+            self.scroll_remainder = 0.0;
+            return scroll.discrete;
+        }
+        if scroll.stop {
+            self.scroll_remainder = 0.0;
+            return 0;
+        }
+        self.scroll_remainder += scroll.absolute;
+        let steps = (self.scroll_remainder / SCROLL_NOTCH).trunc();
+        self.scroll_remainder -= steps * SCROLL_NOTCH;
+        steps as i32
+    }
+
+    // Workspace groups refer to outputs by object; the bar wants names
+    fn name_output(&self, output: &wl_output::WlOutput) {
+        if let Some(workspaces) = &self.ext_workspaces {
+            let name = self.output_state.info(output).and_then(|info| info.name);
+            workspaces.set_output_name(output, name);
         }
     }
 
@@ -113,63 +188,90 @@ impl WaybarTui {
         self.surface = Some(surface);
     }
 
-    fn draw(&mut self, _qh: &QueueHandle<Self>) {
+    // Something a module shows has changed; read them all again and draw
+    fn refresh(&mut self, qh: &QueueHandle<Self>) {
+        self.renderer.lock().unwrap().invalidate();
+        self.draw(qh);
+    }
+
+    fn draw(&mut self, qh: &QueueHandle<Self>) {
         if !self.configured {
             return;
         }
+        
+        // Don't outrun the compositor; the frame callback picks this up
+        if self.frame_pending {
+            self.needs_redraw = true;
+            return;
+        }
+        self.needs_redraw = false;
 
         let width = self.width;
         let height = self.height;
         
-        // Get or create buffer pool
-        let pool = self.pool.get_or_insert_with(|| {
-            SlotPool::new(
-                (width * height * 4) as usize,
-                &self.shm_state,
-            ).expect("Failed to create pool")
-        });
-
-        // Get a buffer - handle errors gracefully
-        let (buffer, canvas) = match pool.create_buffer(
-            width as i32,
-            height as i32,
-            width as i32 * 4,
-            wayland_client::protocol::wl_shm::Format::Argb8888,
-        ) {
-            Ok(b) => b,
+        // Bring the bar up to date; nothing to commit if nothing changed
+        // since the last frame that reached the compositor
+        let renderer = self.renderer.clone();
+        let mut renderer = renderer.lock().unwrap();
+        self.pending_damage.extend(renderer.render(width, height));
+        if self.pending_damage.is_empty() {
+            return;
+        }
+        
+        let swapchain = match &mut self.swapchain {
+            Some(swapchain) => {
+                swapchain.resize(width, height);
+                swapchain
+            }
+            None => match Swapchain::new(&self.shm_state, width, height) {
+                Ok(swapchain) => self.swapchain.insert(swapchain),
+                Err(e) => {
+                    eprintln!("Failed to create buffer pool: {}", e);
+                    return;
+                }
+            },
+        };
+
+        // Get a buffer the compositor has released
+        let (buffer, canvas) = match swapchain.acquire() {
+            Ok(Some(b)) => b,
+            Ok(None) => {
+                // Every buffer is still held. Ask for a frame callback so
+                // something comes back to retry, by when one is usually free.
+                self.needs_redraw = true;
+                if let Some(surface) = &self.surface {
+                    surface.frame(qh, surface.clone());
+                    self.frame_pending = true;
+                    surface.commit();
+                }
+                return;
+            }
             Err(e) => {
                 eprintln!("Failed to create buffer: {}", e);
                 return;
             }
         };
 
-        // Render the bar content
-        self.renderer.lock().unwrap().render(canvas, width, height);
+        // The buffer may hold an older frame, so copy all of it
+        canvas.copy_from_slice(renderer.frame());
 
-        // Attach buffer and damage surface
+        // Attach buffer and damage only what changed
         if let Some(surface) = &self.surface {
             if let Err(e) = buffer.attach_to(surface) {
                 eprintln!("Failed to attach buffer: {}", e);
                 return;
             }
-            surface.damage_buffer(0, 0, width as i32, height as i32);
+            for rect in self.pending_damage.drain(..) {
+                surface.damage_buffer(rect.x, rect.y, rect.width, rect.height);
+            }
             
-            // Request frame callback to schedule next redraw
-            surface.frame(_qh, surface.clone());
+            // Request frame callback to throttle the next redraw
+            surface.frame(qh, surface.clone());
+            self.frame_pending = true;
             
             surface.commit();
         }
     }
-    
-    fn should_redraw(&mut self) -> bool {
-        // Redraw every 200ms for snappy workspace updates
-        if self.last_update.elapsed() >= Duration::from_millis(200) {
-            self.last_update = Instant::now();
-            true
-        } else {
-            false
-        }
-    }
 }
 
 // Implement required trait handlers
@@ -191,8 +293,8 @@ impl CompositorHandler for WaybarTui {
         _surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
-        // Only redraw if enough time has passed
-        if self.should_redraw() {
+        self.frame_pending = false;
+        if self.needs_redraw {
             self.draw(qh);
         }
     }
@@ -216,16 +318,18 @@ impl OutputHandler for WaybarTui {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
+        self.name_output(&output);
     }
 
     fn update_output(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
+        self.name_output(&output);
     }
 
     fn output_destroyed(
@@ -239,7 +343,7 @@ impl OutputHandler for WaybarTui {
 
 impl LayerShellHandler for WaybarTui {
     fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
-        // Handle surface closed
+        self.exit = true;
     }
 
     fn configure(
@@ -256,10 +360,18 @@ impl LayerShellHandler for WaybarTui {
         self.height = h.max(self.height);
 
         self.configured = true;
+        self.frame_pending = false;
         
-        // Request initial frame callback to start the redraw cycle
+        // Let the compositor skip blending whatever is behind an opaque bar
         if let Some(surface) = &self.surface {
-            surface.frame(qh, surface.clone());
+            if self.renderer.lock().unwrap().is_opaque() {
+                if let Ok(region) = Region::new(&self.compositor_state) {
+                    region.add(0, 0, self.width as i32, self.height as i32);
+                    surface.set_opaque_region(Some(region.wl_region()));
+                }
+            } else {
+                surface.set_opaque_region(None);
+            }
         }
         
         self.draw(qh);
@@ -276,10 +388,19 @@ impl SeatHandler for WaybarTui {
     fn new_capability(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: wl_seat::WlSeat,
-        _capability: Capability,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
     ) {
+        if let Some(toplevels) = &self.toplevels {
+            toplevels.set_seat(&seat);
+        }
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            match self.seat_state.get_pointer(qh, &seat) {
+                Ok(pointer) => self.pointer = Some(pointer),
+                Err(e) => eprintln!("Failed to get pointer: {}", e),
+            }
+        }
     }
 
     fn remove_capability(
@@ -287,13 +408,70 @@ impl SeatHandler for WaybarTui {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _seat: wl_seat::WlSeat,
-        _capability: Capability,
+        capability: Capability,
     ) {
+        if capability == Capability::Pointer {
+            if let Some(pointer) = self.pointer.take() {
+                pointer.release();
+            }
+        }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
 }
 
+impl PointerHandler for WaybarTui {
+    fn pointer_frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        _pointer: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            if self.surface.as_ref() != Some(&event.surface) {
+                continue;
+            }
+            let (x, y) = event.position;
+            let handled = match event.kind {
+                PointerEventKind::Press { button, .. } => {
+                    let button = match button {
+                        BTN_LEFT => Button::Left,
+                        BTN_MIDDLE => Button::Middle,
+                        BTN_RIGHT => Button::Right,
+                        _ => continue,
+                    };
+                    self.renderer.lock().unwrap().click(x, y, button)
+                }
+                PointerEventKind::Axis { vertical, .. } => {
+                    let steps = self.scroll_steps(vertical);
+                    self.renderer.lock().unwrap().scroll(x, y, steps)
+                }
+                PointerEventKind::Enter { .. } | PointerEventKind::Motion { .. } => {
+                    self.renderer.lock().unwrap().hover(Some((x, y)))
+                }
+                PointerEventKind::Leave { .. } => self.renderer.lock().unwrap().hover(None),
+                _ => continue,
+            };
+            if handled {
+                self.draw(qh);
+            }
+        }
+    }
+}
+
+impl ExtWorkspaceHandler for WaybarTui {
+    fn ext_workspaces(&self) -> Option<&ExtWorkspaces> {
+        self.ext_workspaces.as_ref()
+    }
+}
+
+impl ForeignToplevelHandler for WaybarTui {
+    fn foreign_toplevels(&self) -> Option<&ForeignToplevels> {
+        self.toplevels.as_ref()
+    }
+}
+
 impl ShmHandler for WaybarTui {
     fn shm_state(&mut self) -> &mut Shm {
         &mut self.shm_state
@@ -313,8 +491,14 @@ delegate_compositor!(WaybarTui);
 delegate_output!(WaybarTui);
 delegate_shm!(WaybarTui);
 delegate_seat!(WaybarTui);
+delegate_pointer!(WaybarTui);
 delegate_layer!(WaybarTui);
 delegate_registry!(WaybarTui);
+delegate_dispatch!(WaybarTui: [ext_workspace::protocol::ext_workspace_manager_v1::ExtWorkspaceManagerV1: ()] => ExtWorkspaces);
+delegate_dispatch!(WaybarTui: [ext_workspace::protocol::ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1: ()] => ExtWorkspaces);
+delegate_dispatch!(WaybarTui: [ext_workspace::protocol::ext_workspace_handle_v1::ExtWorkspaceHandleV1: ()] => ExtWorkspaces);
+delegate_dispatch!(WaybarTui: [ZwlrForeignToplevelManagerV1: ()] => ForeignToplevels);
+delegate_dispatch!(WaybarTui: [ZwlrForeignToplevelHandleV1: ()] => ForeignToplevels);
 
 fn main() -> Result<()> {
     // Handle --version flag
@@ -323,6 +507,9 @@ fn main() -> Result<()> {
         println!("oxidebar {}", env!("CARGO_PKG_VERSION"));
         return Ok(());
     }
+    if args.len() > 1 && args[1] == "render" {
+        return render(&args[2..]);
+    }
     
     // Load configuration
     let config = Config::load();
@@ -338,6 +525,29 @@ fn main() -> Result<()> {
     let layer_shell = LayerShell::bind(&globals, &qh)?;
     let shm_state = Shm::bind(&globals, &qh)?;
     
+    // Compositor IPC when the environment names a socket, otherwise the
+    // standard workspace and window protocols the compositor speaks
+    let mut sources = Sources::system();
+    let (mut ext_workspaces, mut toplevels) = (None, None);
+    if sources.compositor.is_none() {
+        ext_workspaces = ExtWorkspaces::bind(&globals, &qh, &conn);
+        toplevels = ForeignToplevels::bind(&globals, &qh, &conn);
+        sources.compositor = ProtocolCompositor::new(ext_workspaces.clone(), toplevels.clone())
+            .map(|compositor| Box::new(compositor) as Box<dyn Compositor>);
+    }
+    
+    // Compositor events, e.g. workspace switches, redraw immediately
+    let compositor_events = sources.compositor.as_ref().map(|compositor| {
+        let (sender, events) = channel::channel();
+        compositor.spawn_event_stream(Box::new(move || sender.send(()).is_ok()));
+        events
+    });
+    
+    // Backlight changes, whether from scrolling the bar or elsewhere, too
+    let renderer = BarRenderer::with_sources(config, sources);
+    let (sender, backlight_events) = channel::channel();
+    renderer.watch_backlight(Box::new(move || sender.send(()).is_ok()));
+    
     let mut app = WaybarTui::new(
         RegistryState::new(&globals),
         SeatState::new(&globals, &qh),
@@ -345,7 +555,9 @@ fn main() -> Result<()> {
         compositor_state,
         shm_state,
         layer_shell,
-        config,
+        renderer,
+        ext_workspaces,
+        toplevels,
         bar_height,
     );
 
@@ -355,36 +567,130 @@ fn main() -> Result<()> {
     // Initial roundtrip to get initial configure
     event_queue.roundtrip(&mut app)?;
 
-    // Main event loop with faster refresh rate
-    loop {
-        // Dispatch events with error handling
-        match event_queue.dispatch_pending(&mut app) {
-            Ok(_) => {},
-            Err(e) => {
-                eprintln!("Event dispatch error: {}", e);
-                std::thread::sleep(Duration::from_millis(100));
-                continue;
+    // Sleep until a Wayland event, a module timer, a compositor IPC
+    // message or a signal needs attention
+    let mut event_loop: EventLoop<WaybarTui> = EventLoop::try_new()?;
+    let loop_handle = event_loop.handle();
+    
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(loop_handle.clone())
+        .map_err(|e| anyhow::anyhow!("Failed to watch the Wayland socket: {}", e.error))?;
+    
+    // Time-driven modules: the clock, marquees and polled sysfs values
+    let timer_qh = qh.clone();
+    loop_handle
+        .insert_source(Timer::immediate(), move |_, _, app| {
+            app.draw(&timer_qh);
+            let next = app.renderer.lock().unwrap().next_update();
+            TimeoutAction::ToDuration(next)
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to start the module timer: {}", e.error))?;
+    
+    if let Some(events) = compositor_events {
+        let compositor_qh = qh.clone();
+        loop_handle
+            .insert_source(events, move |_, _, app| app.refresh(&compositor_qh))
+            .map_err(|e| anyhow::anyhow!("Failed to watch compositor events: {}", e.error))?;
+    }
+    
+    let backlight_qh = qh.clone();
+    loop_handle
+        .insert_source(backlight_events, move |event, _, app| {
+            if let channel::Event::Msg(()) = event {
+                app.refresh(&backlight_qh);
             }
-        }
-        
-        // Force redraw at configured interval (200ms)
-        if app.should_redraw() {
-            if let Some(surface) = &app.surface {
-                surface.frame(&qh, surface.clone());
-                surface.commit();
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch the backlight: {}", e.error))?;
+    
+    // SIGINT/SIGTERM exit cleanly, SIGUSR1 forces a refresh
+    let (sender, signals) = channel::channel();
+    let mut signal_iter = Signals::new([SIGINT, SIGTERM, SIGUSR1])?;
+    std::thread::spawn(move || {
+        for signal in signal_iter.forever() {
+            if sender.send(signal).is_err() {
+                break;
             }
-            app.draw(&qh);
-        }
-        
-        // Sleep briefly to avoid spinning
-        std::thread::sleep(Duration::from_millis(50));
-        
-        // Flush the connection with error handling
-        if let Err(e) = event_queue.flush() {
-            eprintln!("Flush error: {}. Connection may be broken.", e);
-            break;
         }
+    });
+    let signal_qh = qh.clone();
+    loop_handle
+        .insert_source(signals, move |event, _, app| {
+            if let channel::Event::Msg(signal) = event {
+                match signal {
+                    SIGUSR1 => app.refresh(&signal_qh),
+                    _ => app.exit = true,
+                }
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to watch signals: {}", e.error))?;
+    
+    while !app.exit {
+        event_loop.dispatch(None, &mut app)?;
     }
     
     Ok(())
 }
+
+// oxidebar render [--width N] [--height N] [--out FILE] [--config FILE]
+//                 [--set MODULE=TEXT]... [--state MODULE=normal|warning|critical]...
+//
+// Draws one frame to a PNG without connecting to a compositor
+fn render(args: &[String]) -> Result<()> {
+    let mut width = 1920;
+    let mut height = None;
+    let mut out = String::from("oxidebar.png");
+    let mut config_path = None;
+    let mut overrides: HashMap<String, ModuleOutput> = HashMap::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--width" => width = value()?.parse()?,
+            "--height" => height = Some(value()?.parse()?),
+            "--out" => out = value()?.clone(),
+            "--config" => config_path = Some(value()?.clone()),
+            "--set" => {
+                let (name, text) = split_assignment(value()?)?;
+                overrides.entry(name.to_string()).or_default().text = text.to_string();
+            }
+            "--state" => {
+                let (name, state) = split_assignment(value()?)?;
+                let state = match state {
+                    "normal" => ModuleState::Normal,
+                    "warning" => ModuleState::Warning,
+                    "critical" => ModuleState::Critical,
+                    _ => anyhow::bail!("Unknown module state: {}", state),
+                };
+                overrides.entry(name.to_string()).or_default().state = Some(state);
+            }
+            _ => anyhow::bail!("Unknown render option: {}", arg),
+        }
+    }
+
+    let config = match config_path {
+        Some(path) => Config::from_file(Path::new(&path))?,
+        None => Config::load(),
+    };
+    let height = height.unwrap_or(config.height);
+    if height < TEXT_HEIGHT {
+        anyhow::bail!("The bar must be at least {} pixels high to fit its text", TEXT_HEIGHT);
+    }
+
+    let image = headless::render(config, width, height, overrides);
+    let png = image.to_png()
+        .map_err(|e| anyhow::anyhow!("Failed to encode the bar: {}", e))?;
+    std::fs::write(&out, png)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", out, e))?;
+    eprintln!("Rendered {}x{} bar to {}", width, height, out);
+    Ok(())
+}
+
+fn split_assignment(value: &str) -> Result<(&str, &str)> {
+    value
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected MODULE=VALUE, got {}", value))
+}
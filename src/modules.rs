@@ -1,33 +1,67 @@
+use crate::config::{
+    BacklightConfig, ByteSize, CpuConfig, DiskConfig, DiskDisplay, DiskMount, KeyboardLayoutConfig,
+    MemoryConfig, NetworkConfig, NetworkInterface, TaskbarConfig, TaskbarLabel, TaskbarScope,
+    TemperatureConfig, TemperatureUnit, WindowConfig,
+};
+use crate::compositor::{KeyboardLayouts, Window, Workspace};
+use crate::sources::{DiskUsage, Filesystems, Interfaces};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use std::collections::HashMap;
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
 
 // Module definitions for the bar
 
+/// Severity a module reports, used to pick its colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ModuleState {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Fixed content for a module, shown instead of querying the system.
+/// Used by headless rendering to get reproducible images.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModuleOutput {
+    pub text: String,
+    // None derives the state from the text, as for live data
+    pub state: Option<ModuleState>,
+}
+
 pub struct BatteryModule {
-    battery_path: String,
+    battery_path: PathBuf,
 }
 
 impl BatteryModule {
-    pub fn new() -> Self {
+    /// `sysfs` is where sysfs is mounted, normally /sys
+    pub fn new(sysfs: &Path) -> Self {
         Self {
-            battery_path: Self::find_battery_path(),
+            battery_path: Self::find_battery_path(&sysfs.join("class/power_supply")),
         }
     }
 
-    fn find_battery_path() -> String {
-        let power_supply_path = "/sys/class/power_supply";
-        
+    fn find_battery_path(power_supply_path: &Path) -> PathBuf {
         for entry in ["BAT0", "BAT1"] {
-            let path = format!("{}/{}", power_supply_path, entry);
-            if std::path::Path::new(&path).exists() {
+            let path = power_supply_path.join(entry);
+            if path.exists() {
                 return path;
             }
         }
         
-        format!("{}/BAT0", power_supply_path)
+        power_supply_path.join("BAT0")
     }
 
     fn read_sysfs_u64(&self, filename: &str) -> u64 {
-        let path = format!("{}/{}", self.battery_path, filename);
+        let path = self.battery_path.join(filename);
         fs::read_to_string(&path)
             .ok()
             .and_then(|s| s.trim().parse().ok())
@@ -35,7 +69,7 @@ impl BatteryModule {
     }
 
     fn read_sysfs_string(&self, filename: &str) -> String {
-        let path = format!("{}/{}", self.battery_path, filename);
+        let path = self.battery_path.join(filename);
         fs::read_to_string(&path)
             .unwrap_or_else(|_| String::from("Unknown"))
             .trim()
@@ -57,6 +91,8 @@ impl BatteryModule {
         self.read_sysfs_string("status")
     }
 
+    // One branch per level so each can get its own icon once the font has them
+    #[allow(clippy::if_same_then_else)]
     pub fn get_icon(&self, percentage: f64, status: &str) -> &'static str {
         if status == "Charging" {
             return "CHG"; // Charging
@@ -85,54 +121,1877 @@ impl BatteryModule {
     }
 }
 
+// Rates over less than this are mostly noise, as with cpu usage
+const MIN_NETWORK_SAMPLE: Duration = Duration::from_secs(1);
+
+/// What the network module shows for the interface in use
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetworkReading {
+    pub interface: String,
+    pub wireless: bool,
+    // Bytes per second since the previous sample; 0 for the first one
+    pub down: f64,
+    pub up: f64,
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+    pub gateway: Option<Ipv4Addr>,
+    pub mtu: Option<u32>,
+}
+
+struct NetworkSample {
+    at: DateTime<Local>,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    reading: NetworkReading,
+}
+
 pub struct NetworkModule {
-    // Network monitoring
+    config: NetworkConfig,
+    net_path: PathBuf,
+    procfs: PathBuf,
+    interfaces: Box<dyn Interfaces>,
+    // Rates are the change since this sample
+    last: Mutex<Option<NetworkSample>>,
 }
 
 impl NetworkModule {
-    pub fn new() -> Self {
-        Self {}
+    /// `sysfs` and `procfs` are where those are mounted, normally /sys and /proc
+    pub fn new(config: &NetworkConfig, sysfs: &Path, procfs: &Path, interfaces: Box<dyn Interfaces>) -> Self {
+        Self {
+            config: config.clone(),
+            net_path: sysfs.join("class/net"),
+            procfs: procfs.to_path_buf(),
+            interfaces,
+            last: Mutex::new(None),
+        }
     }
 
+    // The first configured interface that is up, otherwise any that is,
+    // wireless first
     fn get_active_interface(&self) -> Option<String> {
-        // Check common wireless interface names
-        for iface in ["wlan0", "wlp3s0", "wlp2s0", "wlo1"] {
-            let path = format!("/sys/class/net/{}/operstate", iface);
-            if let Ok(state) = fs::read_to_string(&path) {
-                if state.trim() == "up" {
-                    return Some(iface.to_string());
-                }
+        let is_up = |iface: &str| read_trimmed(&self.net_path.join(iface).join("operstate")).as_deref() == Some("up");
+        if let Some(configured) = self.config.interfaces.iter().find(|i| is_up(&i.name)) {
+            return Some(configured.name.clone());
+        }
+        
+        let mut up: Vec<String> = sorted_entries(&self.net_path)
+            .iter()
+            .filter_map(|dir| Some(dir.file_name()?.to_string_lossy().into_owned()))
+            .filter(|iface| iface != "lo" && is_up(iface))
+            .collect();
+        up.sort_by_key(|iface| !self.is_wireless(iface));
+        up.into_iter().next()
+    }
+
+    fn is_wireless(&self, iface: &str) -> bool {
+        self.net_path.join(iface).join("wireless").exists()
+    }
+
+    /// The interface in use with rates since the previous sample; None while
+    /// no interface is up
+    pub fn sample(&self, now: DateTime<Local>) -> Option<NetworkReading> {
+        let iface = self.get_active_interface();
+        let mut last = self.last.lock().unwrap();
+        if let Some(sample) = last.as_ref() {
+            let recent = (now - sample.at).to_std().is_ok_and(|age| age < MIN_NETWORK_SAMPLE);
+            if recent && iface.as_deref() == Some(sample.reading.interface.as_str()) {
+                return Some(sample.reading.clone());
             }
         }
+        let Some(iface) = iface else {
+            *last = None;
+            return None;
+        };
         
-        // Check ethernet
-        for iface in ["eth0", "enp3s0", "enp2s0", "eno1"] {
-            let path = format!("/sys/class/net/{}/operstate", iface);
-            if let Ok(state) = fs::read_to_string(&path) {
-                if state.trim() == "up" {
-                    return Some(iface.to_string());
-                }
+        let dir = self.net_path.join(&iface);
+        let counter = |name: &str| -> u64 {
+            read_trimmed(&dir.join("statistics").join(name)).and_then(|v| v.parse().ok()).unwrap_or(0)
+        };
+        let (rx_bytes, tx_bytes) = (counter("rx_bytes"), counter("tx_bytes"));
+        
+        // Counters restart when an interface does, so a drop counts as nothing
+        let (mut down, mut up) = (0.0, 0.0);
+        if let Some(previous) = last.as_ref().filter(|s| s.reading.interface == iface) {
+            let seconds = (now - previous.at).num_milliseconds() as f64 / 1000.0;
+            if seconds > 0.0 {
+                down = rx_bytes.saturating_sub(previous.rx_bytes) as f64 / seconds;
+                up = tx_bytes.saturating_sub(previous.tx_bytes) as f64 / seconds;
             }
         }
         
-        None
+        let addresses = self.interfaces.addresses(&iface);
+        let reading = NetworkReading {
+            wireless: self.is_wireless(&iface),
+            down,
+            up,
+            ipv4: addresses.iter().find_map(|address| match address {
+                IpAddr::V4(v4) => Some(*v4),
+                IpAddr::V6(_) => None,
+            }),
+            // Link-local addresses only if there's nothing better
+            ipv6: addresses
+                .iter()
+                .filter_map(|address| match address {
+                    IpAddr::V6(v6) => Some(*v6),
+                    IpAddr::V4(_) => None,
+                })
+                .min_by_key(|v6| v6.is_unicast_link_local()),
+            gateway: self.default_gateway(&iface),
+            mtu: read_trimmed(&dir.join("mtu")).and_then(|v| v.parse().ok()),
+            interface: iface,
+        };
+        *last = Some(NetworkSample {
+            at: now,
+            rx_bytes,
+            tx_bytes,
+            reading: reading.clone(),
+        });
+        Some(reading)
     }
 
-    fn is_wireless(&self, iface: &str) -> bool {
-        let wireless_path = format!("/sys/class/net/{}/wireless", iface);
-        std::path::Path::new(&wireless_path).exists()
+    // /proc/net/route lists "Iface Destination Gateway ..." with addresses
+    // as hex of their in-memory value, so the native bytes are in network
+    // order; the default route has destination 0
+    fn default_gateway(&self, iface: &str) -> Option<Ipv4Addr> {
+        let routes = fs::read_to_string(self.procfs.join("net/route")).ok()?;
+        routes.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 || fields[0] != iface || fields[1] != "00000000" {
+                return None;
+            }
+            let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+            Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+        })
     }
 
-    pub fn render(&self) -> String {
-        match self.get_active_interface() {
-            Some(iface) => {
-                if self.is_wireless(&iface) {
-                    format!("WiFi {}", iface)
-                } else {
-                    format!("ETH {}", iface)
+    // The interface's own entry, if it has one
+    fn interface_config(&self, iface: &str) -> Option<&NetworkInterface> {
+        self.config.interfaces.iter().find(|i| i.name == iface)
+    }
+
+    pub fn render(&self, reading: Option<&NetworkReading>) -> String {
+        let Some(reading) = reading else {
+            return self.config.format_disconnected.clone();
+        };
+        let format = self
+            .interface_config(&reading.interface)
+            .and_then(|i| i.format.as_ref())
+            .unwrap_or(&self.config.format);
+        let text = fill_placeholders(format, |name, spec| match name {
+            "icon" if self.config.show_icon => Some(if reading.wireless { "WiFi" } else { "ETH" }.to_string()),
+            "icon" => Some(String::new()),
+            "ifname" => Some(reading.interface.clone()),
+            "down" => format_bytes(reading.down, spec),
+            "up" => format_bytes(reading.up, spec),
+            "ipv4" => Some(reading.ipv4.map(|a| a.to_string()).unwrap_or_default()),
+            "ipv6" => Some(reading.ipv6.map(|a| a.to_string()).unwrap_or_default()),
+            "gateway" => Some(reading.gateway.map(|a| a.to_string()).unwrap_or_default()),
+            "mtu" => Some(reading.mtu.map(|mtu| mtu.to_string()).unwrap_or_default()),
+            _ => None,
+        });
+        // Without an icon, don't leave the space that followed it
+        text.trim().to_string()
+    }
+
+    /// Per-interface thresholds win over the module's; the faster of the two
+    /// directions counts
+    pub fn state(&self, reading: Option<&NetworkReading>) -> ModuleState {
+        let Some(reading) = reading else {
+            return ModuleState::Normal;
+        };
+        let configured = self.interface_config(&reading.interface);
+        let threshold = |own: Option<&ByteSize>, module: Option<&ByteSize>| own.or(module).and_then(ByteSize::bytes);
+        let critical = threshold(
+            configured.and_then(|i| i.critical_threshold.as_ref()),
+            self.config.critical_threshold.as_ref(),
+        );
+        let warning = threshold(
+            configured.and_then(|i| i.warning_threshold.as_ref()),
+            self.config.warning_threshold.as_ref(),
+        );
+        let rate = reading.down.max(reading.up);
+        if critical.is_some_and(|critical| rate >= critical) {
+            ModuleState::Critical
+        } else if warning.is_some_and(|warning| rate >= warning) {
+            ModuleState::Warning
+        } else {
+            ModuleState::Normal
+        }
+    }
+}
+
+// Closer samples than this reuse the previous reading; usage over a few
+// milliseconds is mostly noise
+const MIN_CPU_SAMPLE: Duration = Duration::from_secs(1);
+
+// One bar per core for {graph}, from idle to fully busy
+const GRAPH_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Jiffies one /proc/stat line has counted, busy and in total
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    // "cpu3 user nice system idle iowait irq softirq steal guest guest_nice"
+    fn parse(fields: &str) -> Option<Self> {
+        let values: Vec<u64> = fields
+            .split_whitespace()
+            .map(|v| v.parse().ok())
+            .collect::<Option<_>>()?;
+        if values.len() < 4 {
+            return None;
+        }
+        // Guest time is already part of user time
+        let total: u64 = values.iter().take(8).sum();
+        let idle = values[3] + values.get(4).copied().unwrap_or(0);
+        Some(Self { busy: total - idle, total })
+    }
+
+    // Busy share of the time since `earlier`, in percent
+    fn usage_since(&self, earlier: &CpuTimes) -> f64 {
+        let total = self.total.saturating_sub(earlier.total);
+        if total == 0 {
+            return 0.0;
+        }
+        self.busy.saturating_sub(earlier.busy) as f64 * 100.0 / total as f64
+    }
+}
+
+/// What the cpu module shows, as of its latest sample
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CpuReading {
+    // Percent, overall and per core
+    pub usage: f64,
+    pub cores: Vec<f64>,
+    // GHz, None without cpufreq
+    pub avg_frequency: Option<f64>,
+    pub max_frequency: Option<f64>,
+    pub load: Option<[f64; 3]>,
+}
+
+struct CpuSample {
+    at: DateTime<Local>,
+    total: CpuTimes,
+    cores: Vec<CpuTimes>,
+    reading: CpuReading,
+}
+
+pub struct CpuModule {
+    config: CpuConfig,
+    procfs: PathBuf,
+    sysfs: PathBuf,
+    // Usage is the change since this sample
+    last: Mutex<Option<CpuSample>>,
+}
+
+impl CpuModule {
+    /// `procfs` and `sysfs` are where those are mounted, normally /proc and /sys
+    pub fn new(config: &CpuConfig, procfs: &Path, sysfs: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            procfs: procfs.to_path_buf(),
+            sysfs: sysfs.to_path_buf(),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Usage since the previous sample, or since boot for the first one;
+    /// None if /proc/stat can't be read
+    pub fn sample(&self, now: DateTime<Local>) -> Option<CpuReading> {
+        let mut last = self.last.lock().unwrap();
+        if let Some(sample) = last.as_ref() {
+            if (now - sample.at).to_std().is_ok_and(|age| age < MIN_CPU_SAMPLE) {
+                return Some(sample.reading.clone());
+            }
+        }
+        
+        let stat = fs::read_to_string(self.procfs.join("stat")).ok()?;
+        let mut total = None;
+        let mut cores = Vec::new();
+        for line in stat.lines() {
+            let Some((label, fields)) = line.split_once(' ') else {
+                continue;
+            };
+            if label == "cpu" {
+                total = CpuTimes::parse(fields);
+            } else if label.strip_prefix("cpu").is_some_and(|n| n.parse::<u32>().is_ok()) {
+                cores.push(CpuTimes::parse(fields)?);
+            }
+        }
+        let total = total?;
+        
+        let (previous_total, previous_cores) = match last.as_ref() {
+            Some(sample) => (sample.total, sample.cores.as_slice()),
+            None => (CpuTimes::default(), [].as_slice()),
+        };
+        let core_usage = |(i, core): (usize, &CpuTimes)| {
+            core.usage_since(previous_cores.get(i).unwrap_or(&CpuTimes::default()))
+        };
+        let (avg_frequency, max_frequency) = self.read_frequencies(cores.len());
+        let reading = CpuReading {
+            usage: total.usage_since(&previous_total),
+            cores: cores.iter().enumerate().map(core_usage).collect(),
+            avg_frequency,
+            max_frequency,
+            load: self.read_load(),
+        };
+        
+        *last = Some(CpuSample {
+            at: now,
+            total,
+            cores,
+            reading: reading.clone(),
+        });
+        Some(reading)
+    }
+
+    // Average and highest current frequency over the cores that report one
+    fn read_frequencies(&self, cores: usize) -> (Option<f64>, Option<f64>) {
+        let frequencies: Vec<f64> = (0..cores)
+            .filter_map(|i| {
+                let path = self.sysfs.join(format!("devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq", i));
+                let khz: f64 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+                Some(khz / 1_000_000.0)
+            })
+            .collect();
+        if frequencies.is_empty() {
+            return (None, None);
+        }
+        let average = frequencies.iter().sum::<f64>() / frequencies.len() as f64;
+        (Some(average), frequencies.iter().copied().reduce(f64::max))
+    }
+
+    fn read_load(&self) -> Option<[f64; 3]> {
+        let loadavg = fs::read_to_string(self.procfs.join("loadavg")).ok()?;
+        let mut fields = loadavg.split_whitespace().map(|f| f.parse().ok());
+        Some([fields.next()??, fields.next()??, fields.next()??])
+    }
+
+    pub fn render(&self, reading: &CpuReading) -> String {
+        let or_unknown = |value: Option<f64>, precision: usize| {
+            value.map_or(String::from("?"), |v| format!("{:.*}", precision, v))
+        };
+        let load = |i: usize| or_unknown(reading.load.map(|l| l[i]), 2);
+        let graph: String = reading
+            .cores
+            .iter()
+            .map(|usage| {
+                let level = (usage / 100.0 * GRAPH_BARS.len() as f64) as usize;
+                GRAPH_BARS[level.min(GRAPH_BARS.len() - 1)]
+            })
+            .collect();
+        
+        fill_placeholders(&self.config.format, |name, _| match name {
+            "usage" => Some(format!("{:.0}", reading.usage)),
+            "graph" => Some(graph.clone()),
+            "avg_frequency" => Some(or_unknown(reading.avg_frequency, 1)),
+            "max_frequency" => Some(or_unknown(reading.max_frequency, 1)),
+            "load1" => Some(load(0)),
+            "load5" => Some(load(1)),
+            "load15" => Some(load(2)),
+            // Per core: {usage0}, {usage1}, ...
+            _ => {
+                let core: usize = name.strip_prefix("usage")?.parse().ok()?;
+                reading.cores.get(core).map(|usage| format!("{:.0}", usage))
+            }
+        })
+    }
+
+    /// Severity of the latest sample's overall usage
+    pub fn state(&self, reading: &CpuReading) -> ModuleState {
+        let usage = reading.usage.round();
+        if usage >= self.config.critical_threshold as f64 {
+            ModuleState::Critical
+        } else if usage >= self.config.warning_threshold as f64 {
+            ModuleState::Warning
+        } else {
+            ModuleState::Normal
+        }
+    }
+}
+
+// Binary units for byte placeholders like {used:GiB}
+const BYTE_UNITS: [(&str, f64); 9] = [
+    ("B", 1.0),
+    ("KiB", 1024.0),
+    ("MiB", 1048576.0),
+    ("GiB", 1073741824.0),
+    ("TiB", 1099511627776.0),
+    ("kB", 1e3),
+    ("MB", 1e6),
+    ("GB", 1e9),
+    ("TB", 1e12),
+];
+
+/// `bytes` in `unit` (e.g. "GiB") without a suffix, or scaled to the largest
+/// binary unit that keeps it at 1 or more, with the suffix
+pub fn format_bytes(bytes: f64, unit: Option<&str>) -> Option<String> {
+    let Some(unit) = unit else {
+        let (name, size) = BYTE_UNITS[..5]
+            .iter()
+            .rev()
+            .find(|(_, size)| bytes >= *size)
+            .unwrap_or(&BYTE_UNITS[0]);
+        let precision = if *name == "B" { 0 } else { 1 };
+        return Some(format!("{:.*}{}", precision, bytes / size, name));
+    };
+    let (name, size) = BYTE_UNITS.iter().find(|(name, _)| *name == unit)?;
+    let precision = if *name == "B" { 0 } else { 1 };
+    Some(format!("{:.*}", precision, bytes / size))
+}
+
+/// The inverse of `format_bytes` with a suffix: "5MiB", "1.5GB" or "512B";
+/// a bare number is bytes
+pub fn parse_bytes(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.trim().parse().ok()?;
+    if unit.is_empty() {
+        return Some(number);
+    }
+    let (_, size) = BYTE_UNITS.iter().find(|(name, _)| *name == unit)?;
+    Some(number * size)
+}
+
+/// Replace each `{name}` or `{name:spec}` in `format` with what `value`
+/// returns for it; placeholders it doesn't know are left alone
+pub fn fill_placeholders(format: &str, value: impl Fn(&str, Option<&str>) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let placeholder = &rest[start + 1..start + end];
+        let (name, spec) = match placeholder.split_once(':') {
+            Some((name, spec)) => (name, Some(spec)),
+            None => (placeholder, None),
+        };
+        match value(name, spec) {
+            Some(text) => out.push_str(&text),
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// RAM, swap and zram as of one read, in bytes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryReading {
+    pub total: u64,
+    pub available: u64,
+    pub free: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    // Summed over /sys/block/zram*: RAM holding compressed pages, and the
+    // uncompressed capacity
+    pub zram_used: u64,
+    pub zram_total: u64,
+}
+
+impl MemoryReading {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub fn swap_used(&self) -> u64 {
+        self.swap_total.saturating_sub(self.swap_free)
+    }
+
+    pub fn percentage(&self) -> f64 {
+        percentage(self.used(), self.total)
+    }
+}
+
+fn percentage(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / whole as f64
+}
+
+pub struct MemoryModule {
+    config: MemoryConfig,
+    procfs: PathBuf,
+    sysfs: PathBuf,
+}
+
+impl MemoryModule {
+    /// `procfs` and `sysfs` are where those are mounted, normally /proc and /sys
+    pub fn new(config: &MemoryConfig, procfs: &Path, sysfs: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            procfs: procfs.to_path_buf(),
+            sysfs: sysfs.to_path_buf(),
+        }
+    }
+
+    /// None if /proc/meminfo can't be read
+    pub fn read(&self) -> Option<MemoryReading> {
+        let meminfo = fs::read_to_string(self.procfs.join("meminfo")).ok()?;
+        // "MemTotal:       16318480 kB"
+        let fields: HashMap<&str, u64> = meminfo
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                let kib: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+                Some((key, kib * 1024))
+            })
+            .collect();
+        let field = |key| fields.get(key).copied().unwrap_or(0);
+        
+        let total = *fields.get("MemTotal")?;
+        let (zram_used, zram_total) = self.read_zram();
+        Some(MemoryReading {
+            total,
+            // Kernels before 3.14 don't estimate it
+            available: fields.get("MemAvailable").copied().unwrap_or_else(|| {
+                field("MemFree") + field("Buffers") + field("Cached")
+            }),
+            free: field("MemFree"),
+            buffers: field("Buffers"),
+            cached: field("Cached") + field("SReclaimable"),
+            swap_total: field("SwapTotal"),
+            swap_free: field("SwapFree"),
+            zram_used,
+            zram_total,
+        })
+    }
+
+    fn read_zram(&self) -> (u64, u64) {
+        let Ok(entries) = fs::read_dir(self.sysfs.join("block")) else {
+            return (0, 0);
+        };
+        let mut used = 0;
+        let mut total = 0;
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with("zram") {
+                continue;
+            }
+            let path = entry.path();
+            // mm_stat: orig_data_size compr_data_size mem_used_total ...
+            let mm_stat = fs::read_to_string(path.join("mm_stat")).unwrap_or_default();
+            used += mm_stat.split_whitespace().nth(2).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+            total += fs::read_to_string(path.join("disksize"))
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(0);
+        }
+        (used, total)
+    }
+
+    pub fn render(&self, reading: &MemoryReading) -> String {
+        Self::fill(&self.config.format, reading)
+    }
+
+    fn fill(format: &str, reading: &MemoryReading) -> String {
+        fill_placeholders(format, |name, spec| {
+            let bytes = match name {
+                "percentage" => return Some(format!("{:.0}", reading.percentage())),
+                "swap_percentage" => {
+                    return Some(format!("{:.0}", percentage(reading.swap_used(), reading.swap_total)))
                 }
+                "used" => reading.used(),
+                "available" => reading.available,
+                "total" => reading.total,
+                "free" => reading.free,
+                "buffers" => reading.buffers,
+                "cached" => reading.cached,
+                "swap_used" => reading.swap_used(),
+                "swap_total" => reading.swap_total,
+                "zram_used" => reading.zram_used,
+                "zram_total" => reading.zram_total,
+                _ => return None,
+            };
+            format_bytes(bytes as f64, spec)
+        })
+    }
+
+    /// Where RAM not in use went, from `tooltip_format`; None if that's empty
+    pub fn tooltip(&self, reading: &MemoryReading) -> Option<String> {
+        if self.config.tooltip_format.is_empty() {
+            return None;
+        }
+        Some(Self::fill(&self.config.tooltip_format, reading))
+    }
+
+    /// Severity of the share of RAM in use
+    pub fn state(&self, reading: &MemoryReading) -> ModuleState {
+        let percentage = reading.percentage().round();
+        if percentage >= self.config.critical_threshold as f64 {
+            ModuleState::Critical
+        } else if percentage >= self.config.warning_threshold as f64 {
+            ModuleState::Warning
+        } else {
+            ModuleState::Normal
+        }
+    }
+}
+
+/// One mount as the disk module last read it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MountReading {
+    pub mount: DiskMount,
+    pub usage: DiskUsage,
+    pub state: ModuleState,
+}
+
+pub struct DiskModule {
+    config: DiskConfig,
+    filesystems: Box<dyn Filesystems>,
+}
+
+impl DiskModule {
+    pub fn new(config: &DiskConfig, filesystems: Box<dyn Filesystems>) -> Self {
+        Self {
+            config: config.clone(),
+            filesystems,
+        }
+    }
+
+    /// The mounts `display` picks at `now`, with their usage and state;
+    /// mounts that can't be queried are left out
+    pub fn read(&self, now: DateTime<Local>) -> Vec<MountReading> {
+        let mounts: Vec<_> = self.config.mounts
+            .iter()
+            .filter_map(|mount| {
+                let usage = self.filesystems.usage(Path::new(&mount.path))?;
+                Some(MountReading {
+                    mount: mount.clone(),
+                    usage,
+                    state: self.mount_state(mount, &usage),
+                })
+            })
+            .collect();
+        
+        match self.config.display {
+            DiskDisplay::All => mounts,
+            DiskDisplay::AboveThreshold => mounts
+                .into_iter()
+                .filter(|reading| reading.state != ModuleState::Normal)
+                .collect(),
+            DiskDisplay::Rotate => {
+                if mounts.is_empty() {
+                    return mounts;
+                }
+                let interval = self.config.rotate_interval.max(1) as i64;
+                let turn = (now.timestamp().max(0) / interval) as usize % mounts.len();
+                mounts.into_iter().skip(turn).take(1).collect()
             }
-            None => String::from("NET Down"),
         }
     }
+
+    fn mount_state(&self, mount: &DiskMount, usage: &DiskUsage) -> ModuleState {
+        let percentage = disk_percentage(usage).round();
+        let critical = mount.critical_threshold.unwrap_or(self.config.critical_threshold);
+        let warning = mount.warning_threshold.unwrap_or(self.config.warning_threshold);
+        if percentage >= critical as f64 {
+            ModuleState::Critical
+        } else if percentage >= warning as f64 {
+            ModuleState::Warning
+        } else {
+            ModuleState::Normal
+        }
+    }
+
+    pub fn render(&self, mounts: &[MountReading]) -> String {
+        let texts: Vec<String> = mounts
+            .iter()
+            .map(|MountReading { mount, usage, .. }| {
+                let format = mount.format.as_ref().unwrap_or(&self.config.format);
+                fill_placeholders(format, |name, spec| match name {
+                    "mount" => Some(mount.label.clone().unwrap_or_else(|| mount.path.clone())),
+                    "path" => Some(mount.path.clone()),
+                    "percentage" => Some(format!("{:.0}", disk_percentage(usage))),
+                    "used" => format_bytes(usage.total.saturating_sub(usage.free) as f64, spec),
+                    "free" => format_bytes(usage.available as f64, spec),
+                    "total" => format_bytes(usage.total as f64, spec),
+                    _ => None,
+                })
+            })
+            .collect();
+        texts.join(&self.config.separator)
+    }
+
+    /// The worst state among `mounts`
+    pub fn state(&self, mounts: &[MountReading]) -> ModuleState {
+        mounts
+            .iter()
+            .map(|reading| reading.state)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+// Share in use the way df counts it: the root reserve is neither used nor free
+fn disk_percentage(usage: &DiskUsage) -> f64 {
+    let used = usage.total.saturating_sub(usage.free);
+    percentage(used, used + usage.available)
+}
+
+/// One temperature read, in °C, with the limits the sensor itself reports
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TemperatureReading {
+    pub celsius: f64,
+    pub critical: Option<f64>,
+    pub warning: Option<f64>,
+}
+
+// Where a sensor's values live; hwmon reports temp*_max and temp*_crit,
+// thermal zones "hot" and "critical" trip points
+struct Sensor {
+    input: PathBuf,
+    warning: Option<PathBuf>,
+    critical: Option<PathBuf>,
+}
+
+pub struct TemperatureModule {
+    config: TemperatureConfig,
+    hwmon_path: PathBuf,
+    thermal_path: PathBuf,
+}
+
+impl TemperatureModule {
+    /// `sysfs` is where sysfs is mounted, normally /sys
+    pub fn new(config: &TemperatureConfig, sysfs: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            hwmon_path: sysfs.join("class/hwmon"),
+            thermal_path: sysfs.join("class/thermal"),
+        }
+    }
+
+    /// None if the configured sensor, or any sensor when none is, can't be found
+    pub fn read(&self) -> Option<TemperatureReading> {
+        let sensor = match &self.config.sensor {
+            Some(spec) => self.find_hwmon(spec).or_else(|| self.find_thermal_zone(Some(spec)))?,
+            None => self.find_hwmon_any().or_else(|| self.find_thermal_zone(None))?,
+        };
+        let millidegrees = |path: &Path| -> Option<f64> {
+            let value: f64 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+            Some(value / 1000.0)
+        };
+        Some(TemperatureReading {
+            celsius: millidegrees(&sensor.input)?,
+            critical: sensor.critical.as_deref().and_then(millidegrees),
+            warning: sensor.warning.as_deref().and_then(millidegrees),
+        })
+    }
+
+    // "k10temp/Tctl" picks the input labelled Tctl of the hwmon device named
+    // k10temp; "k10temp" alone its first input
+    fn find_hwmon(&self, spec: &str) -> Option<Sensor> {
+        let (name, label) = match spec.split_once('/') {
+            Some((name, label)) => (name, Some(label)),
+            None => (spec, None),
+        };
+        sorted_entries(&self.hwmon_path)
+            .into_iter()
+            .filter(|dir| read_trimmed(&dir.join("name")).as_deref() == Some(name))
+            .find_map(|dir| {
+                let index = temp_inputs(&dir).into_iter().find(|index| {
+                    label.is_none_or(|label| {
+                        read_trimmed(&dir.join(format!("temp{}_label", index))).as_deref() == Some(label)
+                    })
+                })?;
+                Some(hwmon_sensor(&dir, index))
+            })
+    }
+
+    fn find_hwmon_any(&self) -> Option<Sensor> {
+        sorted_entries(&self.hwmon_path).into_iter().find_map(|dir| {
+            let index = *temp_inputs(&dir).first()?;
+            Some(hwmon_sensor(&dir, index))
+        })
+    }
+
+    // The zone whose type is `zone_type`, or the first zone
+    fn find_thermal_zone(&self, zone_type: Option<&str>) -> Option<Sensor> {
+        let dir = sorted_entries(&self.thermal_path).into_iter().find(|dir| {
+            let is_zone = dir.file_name().is_some_and(|n| n.to_string_lossy().starts_with("thermal_zone"));
+            is_zone && zone_type.is_none_or(|t| read_trimmed(&dir.join("type")).as_deref() == Some(t))
+        })?;
+        
+        let trip_type = |i: u32| read_trimmed(&dir.join(format!("trip_point_{}_type", i)));
+        let trip_point = |kind: &str| {
+            (0..)
+                .map_while(|i| Some((i, trip_type(i)?)))
+                .find(|(_, t)| t == kind)
+                .map(|(i, _)| dir.join(format!("trip_point_{}_temp", i)))
+        };
+        Some(Sensor {
+            input: dir.join("temp"),
+            warning: trip_point("hot"),
+            critical: trip_point("critical"),
+        })
+    }
+
+    pub fn render(&self, reading: &TemperatureReading) -> String {
+        fill_placeholders(&self.config.format, |name, _| match name {
+            "temperature" => Some(format!("{:.0}", self.convert(reading.celsius))),
+            "unit" => Some(self.config.unit.symbol().to_string()),
+            _ => None,
+        })
+    }
+
+    /// Configured thresholds win over the sensor's own; without a warning
+    /// limit from either, warn 10 °C short of critical
+    pub fn state(&self, reading: &TemperatureReading) -> ModuleState {
+        let temperature = self.convert(reading.celsius);
+        let critical = self.config.critical_threshold.or(reading.critical.map(|c| self.convert(c)));
+        let warning = self.config.warning_threshold
+            .or(reading.warning.map(|w| self.convert(w)))
+            .or(reading.critical.map(|c| self.convert(c - 10.0)));
+        if critical.is_some_and(|critical| temperature >= critical) {
+            ModuleState::Critical
+        } else if warning.is_some_and(|warning| temperature >= warning) {
+            ModuleState::Warning
+        } else {
+            ModuleState::Normal
+        }
+    }
+
+    fn convert(&self, celsius: f64) -> f64 {
+        match self.config.unit {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+fn hwmon_sensor(dir: &Path, index: u32) -> Sensor {
+    let limit = |kind: &str| {
+        let path = dir.join(format!("temp{}_{}", index, kind));
+        path.exists().then_some(path)
+    };
+    Sensor {
+        input: dir.join(format!("temp{}_input", index)),
+        warning: limit("max"),
+        critical: limit("crit"),
+    }
+}
+
+// Indices of the temp*_input files in an hwmon directory, in order
+fn temp_inputs(dir: &Path) -> Vec<u32> {
+    let mut indices: Vec<u32> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.strip_prefix("temp")?.strip_suffix("_input")?.parse().ok()
+        })
+        .collect();
+    indices.sort_unstable();
+    indices
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    Some(fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+/// One backlight read, in the device's raw units
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BacklightReading {
+    pub device: String,
+    pub brightness: u64,
+    pub max: u64,
+}
+
+impl BacklightReading {
+    pub fn percentage(&self) -> f64 {
+        percentage(self.brightness, self.max)
+    }
+}
+
+pub struct BacklightModule {
+    config: BacklightConfig,
+    backlight_path: PathBuf,
+}
+
+impl BacklightModule {
+    /// `sysfs` is where sysfs is mounted, normally /sys
+    pub fn new(config: &BacklightConfig, sysfs: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            backlight_path: sysfs.join("class/backlight"),
+        }
+    }
+
+    /// The configured device's directory, or the first one
+    pub fn device(&self) -> Option<PathBuf> {
+        match &self.config.device {
+            Some(device) => Some(self.backlight_path.join(device)).filter(|dir| dir.exists()),
+            None => sorted_entries(&self.backlight_path).into_iter().next(),
+        }
+    }
+
+    pub fn read(&self) -> Option<BacklightReading> {
+        let dir = self.device()?;
+        let value = |name: &str| read_trimmed(&dir.join(name))?.parse().ok();
+        Some(BacklightReading {
+            device: dir.file_name()?.to_string_lossy().into_owned(),
+            // What the hardware is actually at, where the driver says
+            brightness: value("actual_brightness").or_else(|| value("brightness"))?,
+            // Without levels there's nothing to show or step through
+            max: value("max_brightness").filter(|&max| max > 0)?,
+        })
+    }
+
+    pub fn render(&self, reading: &BacklightReading) -> String {
+        fill_placeholders(&self.config.format, |name, _| match name {
+            "percentage" => Some(format!("{:.0}", reading.percentage())),
+            "brightness" => Some(reading.brightness.to_string()),
+            "max" => Some(reading.max.to_string()),
+            "device" => Some(reading.device.clone()),
+            _ => None,
+        })
+    }
+
+    /// The raw brightness `steps` scroll notches up from `reading` (down when
+    /// negative). Moves at least one raw unit so coarse devices still react,
+    /// and stops short of 0 since a black screen is hard to scroll back from.
+    pub fn stepped(&self, reading: &BacklightReading, steps: i32) -> u64 {
+        let target = reading.percentage() + f64::from(steps) * f64::from(self.config.step);
+        let mut value = (target / 100.0 * reading.max as f64).round() as i64;
+        let current = reading.brightness as i64;
+        if value == current {
+            value += i64::from(steps.signum());
+        }
+        let max = reading.max as i64;
+        value.clamp(1.min(current).min(max), max) as u64
+    }
+
+    /// How to set the device to `value`: the configured command, or logind's
+    /// SetBrightness, which needs no root for the session's own user
+    pub fn set_command(&self, reading: &BacklightReading, value: u64) -> Command {
+        match &self.config.command {
+            Some(command) => {
+                let percentage = percentage(value, reading.max);
+                let command = fill_placeholders(command, |name, _| match name {
+                    "percentage" => Some(format!("{:.0}", percentage)),
+                    "value" => Some(value.to_string()),
+                    "device" => Some(reading.device.clone()),
+                    _ => None,
+                });
+                let mut sh = Command::new("sh");
+                sh.arg("-c").arg(command);
+                sh
+            }
+            None => {
+                let mut busctl = Command::new("busctl");
+                busctl.args([
+                    "call",
+                    "--system",
+                    "org.freedesktop.login1",
+                    "/org/freedesktop/login1/session/auto",
+                    "org.freedesktop.login1.Session",
+                    "SetBrightness",
+                    "ssu",
+                    "backlight",
+                    &reading.device,
+                    &value.to_string(),
+                ]);
+                busctl
+            }
+        }
+    }
+
+    /// Move the brightness `steps` notches; the change itself happens in the
+    /// background and shows up through the brightness file
+    pub fn adjust(&self, steps: i32) -> anyhow::Result<()> {
+        let Some(reading) = self.read() else {
+            anyhow::bail!("no backlight device found");
+        };
+        let value = self.stepped(&reading, steps);
+        if value == reading.brightness {
+            return Ok(());
+        }
+        let mut child = self.set_command(&reading, value).spawn()?;
+        std::thread::spawn(move || match child.wait() {
+            Ok(status) if !status.success() => eprintln!("Failed to set brightness: {}", status),
+            Err(e) => eprintln!("Failed to set brightness: {}", e),
+            Ok(_) => {}
+        });
+        Ok(())
+    }
+}
+
+struct CompiledRewrite {
+    app_id: Option<Regex>,
+    pattern: Regex,
+    replacement: String,
+}
+
+pub struct WindowModule {
+    config: WindowConfig,
+    rewrites: Vec<CompiledRewrite>,
+}
+
+impl WindowModule {
+    pub fn new(config: &WindowConfig) -> Self {
+        let compile = |pattern: &str| {
+            Regex::new(pattern)
+                .map_err(|e| eprintln!("Ignoring window rewrite rule: bad regex {:?}: {}", pattern, e))
+                .ok()
+        };
+        
+        let rewrites = config.rewrite
+            .iter()
+            .filter_map(|rule| {
+                let app_id = match &rule.app_id {
+                    Some(app_id) => Some(compile(app_id)?),
+                    None => None,
+                };
+                Some(CompiledRewrite {
+                    app_id,
+                    pattern: compile(&rule.pattern)?,
+                    replacement: rule.replacement.clone(),
+                })
+            })
+            .collect();
+        
+        Self {
+            config: config.clone(),
+            rewrites,
+        }
+    }
+
+    /// Text for the focused window, or the empty text when there is none
+    pub fn render(&self, window: Option<&Window>) -> String {
+        let Some(window) = window else {
+            return self.config.empty.clone();
+        };
+        let app_id = window.app_id.as_deref().unwrap_or("");
+        
+        // Every matching rule applies, in order
+        let mut title = window.title.clone().unwrap_or_default();
+        for rule in &self.rewrites {
+            if rule.app_id.as_ref().is_none_or(|re| re.is_match(app_id)) {
+                title = rule.pattern.replace_all(&title, rule.replacement.as_str()).into_owned();
+            }
+        }
+        
+        let text = self.config.format
+            .replace("{title}", &title)
+            .replace("{app_id}", app_id);
+        truncate(&text, self.config.max_length as usize)
+    }
+}
+
+/// Where one window's label sits in the taskbar text
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskbarEntry {
+    pub window_id: u64,
+    pub graphemes: Range<usize>,
+}
+
+pub struct TaskbarModule {
+    config: TaskbarConfig,
+}
+
+impl TaskbarModule {
+    pub fn new(config: &TaskbarConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// Labels for the windows in scope, the focused one in brackets like the
+    /// focused workspace, along with where each label ended up
+    pub fn render(&self, windows: &[Window], workspaces: &[Workspace]) -> (String, Vec<TaskbarEntry>) {
+        let focused_workspace = workspaces.iter().find(|w| w.is_focused).map(|w| w.id);
+        let workspace_idx = |id: Option<u64>| {
+            workspaces.iter().find(|w| Some(w.id) == id).map_or(u32::MAX, |w| w.idx)
+        };
+        
+        // Without workspace info (wlr-foreign-toplevel) every window is in scope
+        let placed = windows.iter().any(|w| w.workspace_id.is_some());
+        
+        let mut shown: Vec<&Window> = windows
+            .iter()
+            .filter(|w| match self.config.scope {
+                TaskbarScope::Workspace if !placed => true,
+                TaskbarScope::Workspace => w.workspace_id.is_some() && w.workspace_id == focused_workspace,
+                TaskbarScope::All => true,
+            })
+            .collect();
+        // Workspace order, then opening order
+        shown.sort_by_key(|w| (workspace_idx(w.workspace_id), w.id));
+        
+        let mut text = String::new();
+        let mut entries = Vec::new();
+        let mut length = 0;
+        for window in shown {
+            if !text.is_empty() {
+                text.push(' ');
+                length += 1;
+            }
+            let label = self.label(window);
+            let label = if window.is_focused { format!("[{}]", label) } else { label };
+            let label_length = label.graphemes(true).count();
+            
+            entries.push(TaskbarEntry {
+                window_id: window.id,
+                graphemes: length..length + label_length,
+            });
+            text.push_str(&label);
+            length += label_length;
+        }
+        (text, entries)
+    }
+
+    fn label(&self, window: &Window) -> String {
+        let app_id = window.app_id.as_deref().unwrap_or("");
+        if let Some(icon) = self.config.icons.get(app_id) {
+            return icon.clone();
+        }
+        
+        let label = match self.config.label {
+            TaskbarLabel::Abbrev => abbreviate(app_id, self.config.abbrev_length as usize),
+            TaskbarLabel::AppId => app_id.to_string(),
+            TaskbarLabel::Title => window.title.clone().unwrap_or_default(),
+        };
+        if label.is_empty() {
+            return String::from("?");
+        }
+        truncate(&label, self.config.max_length as usize)
+    }
+}
+
+pub struct KeyboardLayoutModule {
+    config: KeyboardLayoutConfig,
+}
+
+impl KeyboardLayoutModule {
+    pub fn new(config: &KeyboardLayoutConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// Text for the active layout; empty when niri reports none
+    pub fn render(&self, layouts: &KeyboardLayouts) -> String {
+        let Some(name) = layouts.names.get(layouts.current_idx) else {
+            return String::new();
+        };
+        let label = self.config.labels.get(name).unwrap_or(name);
+        
+        self.config.format
+            .replace("{label}", label)
+            .replace("{name}", name)
+    }
+}
+
+// "org.gnome.Nautilus" -> "nau"; reverse-DNS ids are named by their last part
+fn abbreviate(app_id: &str, length: usize) -> String {
+    let name = app_id.rsplit('.').next().unwrap_or(app_id);
+    name.to_lowercase().graphemes(true).take(length).collect()
+}
+
+// Cut `text` to at most `max` graphemes, ending in an ellipsis; 0 = no limit
+fn truncate(text: &str, max: usize) -> String {
+    if max == 0 || text.graphemes(true).count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.graphemes(true).take(max - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BacklightConfig, KeyboardLayoutConfig, RewriteRule, TaskbarConfig};
+    use crate::config::Config;
+    use crate::sources::{FixedFilesystems, FixedInterfaces};
+    use crate::testing::Fixture;
+    use chrono::TimeZone;
+
+    #[test]
+    fn battery_reads_charge_and_status() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/power_supply/BAT0/charge_now", "3000000\n")
+            .write("class/power_supply/BAT0/charge_full", "4000000\n")
+            .write("class/power_supply/BAT0/status", "Discharging\n");
+
+        assert_eq!(BatteryModule::new(sysfs.path()).render(), "BAT 75%");
+
+        sysfs.write("class/power_supply/BAT0/status", "Charging\n");
+        assert_eq!(BatteryModule::new(sysfs.path()).render(), "CHG 75%");
+    }
+
+    #[test]
+    fn battery_falls_back_to_bat1() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/power_supply/BAT1/charge_now", "500\n")
+            .write("class/power_supply/BAT1/charge_full", "1000\n");
+
+        assert_eq!(BatteryModule::new(sysfs.path()).render(), "BAT 50%");
+    }
+
+    #[test]
+    fn missing_battery_reads_as_empty() {
+        let sysfs = Fixture::new();
+
+        assert_eq!(BatteryModule::new(sysfs.path()).render(), "LOW 0%");
+    }
+
+    // /proc/stat with the given (busy, idle) jiffies overall and per core
+    fn stat(total: (u64, u64), cores: &[(u64, u64)]) -> String {
+        let line = |label: &str, (busy, idle): (u64, u64)| {
+            format!("{} {} 0 0 {} 0 0 0 0 0 0\n", label, busy, idle)
+        };
+        let mut stat = line("cpu ", total);
+        for (i, core) in cores.iter().enumerate() {
+            stat.push_str(&line(&format!("cpu{}", i), *core));
+        }
+        stat + "intr 12345 0 0\nctxt 6789\n"
+    }
+
+    fn cpu(format: &str, procfs: &Fixture) -> CpuModule {
+        let config = CpuConfig {
+            format: format.to_string(),
+            ..CpuConfig::default()
+        };
+        CpuModule::new(&config, procfs.path(), procfs.path())
+    }
+
+    #[test]
+    fn cpu_usage_is_the_change_between_samples() {
+        let procfs = Fixture::new();
+        procfs.write("stat", &stat((100, 300), &[(50, 150), (50, 150)]));
+        let module = cpu("{usage}% {usage0}/{usage1}", &procfs);
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        // The first sample covers the time since boot
+        let reading = module.sample(start).unwrap();
+        assert_eq!(module.render(&reading), "25% 25/25");
+
+        // One core busy, the other idle
+        procfs.write("stat", &stat((200, 400), &[(150, 150), (50, 250)]));
+        let soon = start + chrono::Duration::milliseconds(500);
+        assert_eq!(module.render(&module.sample(soon).unwrap()), "25% 25/25");
+        let later = start + chrono::Duration::seconds(2);
+        assert_eq!(module.render(&module.sample(later).unwrap()), "50% 100/0");
+
+        procfs.write("stat", "garbage\n");
+        assert_eq!(module.sample(later + chrono::Duration::seconds(2)), None);
+    }
+
+    #[test]
+    fn cpu_frequency_load_and_graph() {
+        let procfs = Fixture::new();
+        procfs
+            .write("loadavg", "0.52 1.10 2.00 2/345 6789\n")
+            .write("devices/system/cpu/cpu0/cpufreq/scaling_cur_freq", "1200000\n")
+            .write("devices/system/cpu/cpu1/cpufreq/scaling_cur_freq", "3000000\n");
+        let module = cpu("{avg_frequency} {max_frequency} {load1} {load15} {graph}", &procfs);
+
+        let reading = CpuReading {
+            cores: vec![0.0, 40.0, 99.0],
+            ..CpuReading::default()
+        };
+        assert_eq!(module.render(&reading), "? ? ? ? ▁▄█");
+
+        procfs.write("stat", &stat((0, 100), &[(0, 50), (0, 50)]));
+        let reading = module.sample(Local::now()).unwrap();
+        assert_eq!(module.render(&reading), "2.1 3.0 0.52 2.00 ▁▁");
+    }
+
+    #[test]
+    fn byte_placeholders_take_a_unit() {
+        let format = |format: &str| {
+            fill_placeholders(format, |name, unit| match name {
+                "size" => format_bytes(3.5 * 1024.0 * 1024.0 * 1024.0, unit),
+                _ => None,
+            })
+        };
+        assert_eq!(format("{size} {size:GiB} {size:MiB} {size:GB}"), "3.5GiB 3.5 3584.0 3.8");
+        // Unknown names and units, and stray braces, stay as they are
+        assert_eq!(format("{other} {size:furlongs} {size"), "{other} {size:furlongs} {size");
+        assert_eq!(format_bytes(512.0, None).unwrap(), "512B");
+
+        assert_eq!(parse_bytes("5MiB"), Some(5.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_bytes("1.5 kB"), Some(1500.0));
+        assert_eq!(parse_bytes("512"), Some(512.0));
+        assert_eq!(parse_bytes("5 furlongs"), None);
+    }
+
+    fn meminfo(available: u64) -> String {
+        format!(
+            "MemTotal:       16000000 kB\n\
+             MemFree:         2000000 kB\n\
+             MemAvailable:    {} kB\n\
+             Buffers:          500000 kB\n\
+             Cached:          3000000 kB\n\
+             SReclaimable:     500000 kB\n\
+             SwapTotal:       8000000 kB\n\
+             SwapFree:        6000000 kB\n",
+            available
+        )
+    }
+
+    #[test]
+    fn memory_reads_meminfo_and_zram() {
+        let fs = Fixture::new();
+        fs.write("meminfo", &meminfo(4000000))
+            .write("block/zram0/disksize", "8589934592\n")
+            .write("block/zram0/mm_stat", "2147483648 536870912 549453824 0 549453824 0 0 0 0\n")
+            .write("block/sda/size", "1000\n");
+        let config = MemoryConfig {
+            format: "{used:GiB}/{total:GiB} {percentage}% {cached:MiB} {swap_percentage}% {zram_used} {zram_total}"
+                .to_string(),
+            ..MemoryConfig::default()
+        };
+        let module = MemoryModule::new(&config, fs.path(), fs.path());
+
+        let reading = module.read().unwrap();
+        assert_eq!(module.render(&reading), "11.4/15.3 75% 3418.0 25% 524.0MiB 8.0GiB");
+        assert_eq!(module.state(&reading), ModuleState::Normal);
+        assert_eq!(module.tooltip(&reading).unwrap(), "buf 488.3MiB cache 3.3GiB free 1.9GiB");
+        let quiet = MemoryModule::new(&MemoryConfig { tooltip_format: String::new(), ..config }, fs.path(), fs.path());
+        assert_eq!(quiet.tooltip(&reading), None);
+
+        fs.write("meminfo", &meminfo(2400000));
+        assert_eq!(module.state(&module.read().unwrap()), ModuleState::Warning);
+        fs.write("meminfo", &meminfo(100000));
+        assert_eq!(module.state(&module.read().unwrap()), ModuleState::Critical);
+
+        fs.write("meminfo", "garbage\n");
+        assert_eq!(module.read(), None);
+    }
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    // `used` of `total` GiB in use, with a 1 GiB root reserve
+    fn disk_usage(used: u64, total: u64) -> DiskUsage {
+        DiskUsage {
+            total: total * GIB,
+            free: (total - used) * GIB,
+            available: (total - used).saturating_sub(1) * GIB,
+        }
+    }
+
+    fn mount(path: &str) -> DiskMount {
+        DiskMount {
+            path: path.to_string(),
+            label: None,
+            format: None,
+            warning_threshold: None,
+            critical_threshold: None,
+        }
+    }
+
+    fn disks(config: DiskConfig) -> (DiskModule, FixedFilesystems) {
+        let filesystems = FixedFilesystems::default();
+        filesystems.set("/", disk_usage(40, 101));
+        filesystems.set("/home", disk_usage(850, 1001));
+        filesystems.set("/boot", disk_usage(1, 2));
+        (DiskModule::new(&config, Box::new(filesystems.clone())), filesystems)
+    }
+
+    #[test]
+    fn disk_mounts_have_their_own_format_and_thresholds() {
+        let config = DiskConfig {
+            format: "{mount} {percentage}%".to_string(),
+            mounts: vec![
+                mount("/"),
+                DiskMount {
+                    label: Some("home".to_string()),
+                    format: Some("{mount} {free:GiB}/{total}".to_string()),
+                    critical_threshold: Some(85),
+                    ..mount("/home")
+                },
+                mount("/unmounted"),
+            ],
+            ..DiskConfig::default()
+        };
+        let (module, filesystems) = disks(config);
+        let mounts = module.read(Local::now());
+
+        assert_eq!(module.render(&mounts), "/ 40% home 150.0/1001.0GiB");
+        assert_eq!(module.state(&mounts), ModuleState::Critical);
+
+        filesystems.set("/home", disk_usage(100, 1001));
+        assert_eq!(module.state(&module.read(Local::now())), ModuleState::Normal);
+    }
+
+    #[test]
+    fn disk_can_rotate_or_show_only_full_mounts() {
+        let config = DiskConfig {
+            mounts: vec![mount("/"), mount("/home"), mount("/boot")],
+            display: DiskDisplay::Rotate,
+            rotate_interval: 10,
+            ..DiskConfig::default()
+        };
+        let (module, _) = disks(config.clone());
+        let at = |seconds| module.read(Local.timestamp_opt(seconds, 0).unwrap());
+        assert_eq!(module.render(&at(990)), "/ 40%");
+        assert_eq!(module.render(&at(1009)), "/home 85%");
+        assert_eq!(module.state(&at(1009)), ModuleState::Warning);
+        assert_eq!(module.render(&at(1010)), "/boot 100%");
+        assert_eq!(module.render(&at(1020)), "/ 40%");
+
+        let config = DiskConfig {
+            display: DiskDisplay::AboveThreshold,
+            separator: ", ".to_string(),
+            ..config
+        };
+        let (module, filesystems) = disks(config);
+        let at = |seconds| module.read(Local.timestamp_opt(seconds, 0).unwrap());
+        assert_eq!(module.render(&at(0)), "/home 85%, /boot 100%");
+        filesystems.set("/home", disk_usage(1, 1001));
+        filesystems.set("/boot", disk_usage(0, 2));
+        assert_eq!(module.render(&at(0)), "");
+    }
+
+    fn temperature(sensor: Option<&str>, unit: TemperatureUnit, sysfs: &Fixture) -> TemperatureModule {
+        let config = TemperatureConfig {
+            sensor: sensor.map(str::to_string),
+            unit,
+            ..TemperatureConfig::default()
+        };
+        TemperatureModule::new(&config, sysfs.path())
+    }
+
+    #[test]
+    fn temperature_sensors_are_picked_by_name_and_label() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/hwmon/hwmon0/name", "nvme\n")
+            .write("class/hwmon/hwmon0/temp1_input", "38850\n")
+            .write("class/hwmon/hwmon1/name", "k10temp\n")
+            .write("class/hwmon/hwmon1/temp1_input", "61250\n")
+            .write("class/hwmon/hwmon1/temp1_label", "Tctl\n")
+            .write("class/hwmon/hwmon1/temp3_input", "55000\n")
+            .write("class/hwmon/hwmon1/temp3_label", "Tccd1\n")
+            .write("class/hwmon/hwmon1/temp3_crit", "95000\n")
+            .write("class/thermal/thermal_zone0/type", "acpitz\n")
+            .write("class/thermal/thermal_zone0/temp", "27800\n");
+        let read = |sensor| {
+            let module = temperature(sensor, TemperatureUnit::Celsius, &sysfs);
+            module.read().map(|reading| module.render(&reading))
+        };
+
+        assert_eq!(read(None).as_deref(), Some("39C"));
+        assert_eq!(read(Some("k10temp")).as_deref(), Some("61C"));
+        assert_eq!(read(Some("k10temp/Tccd1")).as_deref(), Some("55C"));
+        assert_eq!(read(Some("acpitz")).as_deref(), Some("28C"));
+        assert_eq!(read(Some("k10temp/Tdie")), None);
+
+        let fahrenheit = temperature(Some("k10temp/Tccd1"), TemperatureUnit::Fahrenheit, &sysfs);
+        assert_eq!(fahrenheit.render(&fahrenheit.read().unwrap()), "131F");
+    }
+
+    #[test]
+    fn temperature_states_default_to_the_sensor_limits() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/hwmon/hwmon0/name", "coretemp\n")
+            .write("class/hwmon/hwmon0/temp1_input", "79000\n")
+            .write("class/hwmon/hwmon0/temp1_max", "80000\n")
+            .write("class/hwmon/hwmon0/temp1_crit", "100000\n")
+            .write("class/thermal/thermal_zone0/type", "x86_pkg_temp\n")
+            .write("class/thermal/thermal_zone0/temp", "91000\n")
+            .write("class/thermal/thermal_zone0/trip_point_0_type", "passive\n")
+            .write("class/thermal/thermal_zone0/trip_point_0_temp", "85000\n")
+            .write("class/thermal/thermal_zone0/trip_point_1_type", "critical\n")
+            .write("class/thermal/thermal_zone0/trip_point_1_temp", "100000\n");
+        let state = |module: &TemperatureModule| module.state(&module.read().unwrap());
+
+        let coretemp = temperature(Some("coretemp"), TemperatureUnit::Fahrenheit, &sysfs);
+        assert_eq!(state(&coretemp), ModuleState::Normal);
+        sysfs.write("class/hwmon/hwmon0/temp1_input", "80000\n");
+        assert_eq!(state(&coretemp), ModuleState::Warning);
+        sysfs.write("class/hwmon/hwmon0/temp1_input", "100000\n");
+        assert_eq!(state(&coretemp), ModuleState::Critical);
+
+        // No "hot" trip point, so warn 10 degrees short of critical
+        let zone = temperature(Some("x86_pkg_temp"), TemperatureUnit::Celsius, &sysfs);
+        assert_eq!(state(&zone), ModuleState::Warning);
+
+        // Configured thresholds are in the configured unit
+        let config = TemperatureConfig {
+            sensor: Some("x86_pkg_temp".to_string()),
+            unit: TemperatureUnit::Fahrenheit,
+            critical_threshold: Some(190.0),
+            ..TemperatureConfig::default()
+        };
+        let zone = TemperatureModule::new(&config, sysfs.path());
+        assert_eq!(state(&zone), ModuleState::Critical);
+    }
+
+    #[test]
+    fn backlight_reads_the_configured_or_first_device() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/backlight/acpi_video0/brightness", "3\n")
+            .write("class/backlight/acpi_video0/max_brightness", "15\n")
+            .write("class/backlight/intel_backlight/brightness", "19200\n")
+            .write("class/backlight/intel_backlight/max_brightness", "96000\n")
+            .write("class/backlight/nvidia_0/brightness", "4\n")
+            .write("class/backlight/nvidia_0/actual_brightness", "6\n")
+            .write("class/backlight/nvidia_0/max_brightness", "15\n")
+            .write("class/backlight/unlit/brightness", "0\n")
+            .write("class/backlight/unlit/max_brightness", "0\n");
+        let read = |device: Option<&str>| {
+            let config = BacklightConfig {
+                format: "{device} {percentage}% {brightness}/{max}".to_string(),
+                device: device.map(str::to_string),
+                ..BacklightConfig::default()
+            };
+            let module = BacklightModule::new(&config, sysfs.path());
+            module.read().map(|reading| module.render(&reading))
+        };
+
+        assert_eq!(read(None).as_deref(), Some("acpi_video0 20% 3/15"));
+        assert_eq!(read(Some("intel_backlight")).as_deref(), Some("intel_backlight 20% 19200/96000"));
+        assert_eq!(read(Some("amdgpu_bl0")), None);
+        // The hardware's own level wins over the requested one
+        assert_eq!(read(Some("nvidia_0")).as_deref(), Some("nvidia_0 40% 6/15"));
+        assert_eq!(read(Some("unlit")), None);
+    }
+
+    #[test]
+    fn backlight_steps_stay_on_screen() {
+        let module = BacklightModule::new(&BacklightConfig::default(), Path::new("/nonexistent"));
+        let reading = |brightness, max| BacklightReading {
+            device: "intel_backlight".to_string(),
+            brightness,
+            max,
+        };
+
+        assert_eq!(module.stepped(&reading(19200, 96000), 1), 24000);
+        assert_eq!(module.stepped(&reading(19200, 96000), -2), 9600);
+        assert_eq!(module.stepped(&reading(96000, 96000), 1), 96000);
+        assert_eq!(module.stepped(&reading(2000, 96000), -1), 1);
+        // 5% of 10 levels is half a level, which can round to no change
+        assert_eq!(module.stepped(&reading(7, 10), 1), 8);
+        assert_eq!(module.stepped(&reading(7, 10), -1), 6);
+        // Already off stays reachable from below
+        assert_eq!(module.stepped(&reading(0, 15), -1), 0);
+        assert_eq!(module.stepped(&reading(3, 0), 1), 0);
+    }
+
+    #[test]
+    fn backlight_is_set_through_logind_or_a_command() {
+        let args = |command: &Command| -> Vec<String> {
+            std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+        let reading = BacklightReading {
+            device: "intel_backlight".to_string(),
+            brightness: 19200,
+            max: 96000,
+        };
+
+        let logind = BacklightModule::new(&BacklightConfig::default(), Path::new("/nonexistent"));
+        let busctl = args(&logind.set_command(&reading, 24000));
+        assert_eq!(busctl[0], "busctl");
+        assert_eq!(busctl[busctl.len() - 4..], ["ssu", "backlight", "intel_backlight", "24000"]);
+
+        let config = BacklightConfig {
+            command: Some("brightnessctl -d {device} set {percentage}% # {value}".to_string()),
+            ..BacklightConfig::default()
+        };
+        let custom = BacklightModule::new(&config, Path::new("/nonexistent"));
+        assert_eq!(
+            args(&custom.set_command(&reading, 24000)),
+            ["sh", "-c", "brightnessctl -d intel_backlight set 25% # 24000"]
+        );
+    }
+
+    fn network(config: &NetworkConfig, fs: &Fixture, interfaces: &FixedInterfaces) -> NetworkModule {
+        NetworkModule::new(config, fs.path(), fs.path(), Box::new(interfaces.clone()))
+    }
+
+    #[test]
+    fn network_prefers_wireless_interfaces() {
+        let sysfs = Fixture::new();
+        sysfs
+            .write("class/net/lo/operstate", "up\n")
+            .write("class/net/eth0/operstate", "up\n")
+            .write("class/net/wlan0/operstate", "up\n")
+            .mkdir("class/net/wlan0/wireless");
+        let module = network(&NetworkConfig::default(), &sysfs, &FixedInterfaces::default());
+        let render = |seconds| module.render(module.sample(Local.timestamp_opt(seconds, 0).unwrap()).as_ref());
+
+        assert_eq!(render(0), "WiFi wlan0");
+
+        sysfs.write("class/net/wlan0/operstate", "down\n");
+        assert_eq!(render(10), "ETH eth0");
+
+        sysfs.write("class/net/eth0/operstate", "dormant\n");
+        assert_eq!(render(20), "NET Down");
+    }
+
+    #[test]
+    fn network_rates_addresses_and_routes() {
+        let fs = Fixture::new();
+        fs.write("class/net/enp5s0/operstate", "up\n")
+            .write("class/net/enp5s0/mtu", "1500\n")
+            .write("class/net/enp5s0/statistics/rx_bytes", "1000000\n")
+            .write("class/net/enp5s0/statistics/tx_bytes", "5000\n")
+            .write(
+                "net/route",
+                "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                 enp5s0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+                 enp5s0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\n",
+            );
+        let interfaces = FixedInterfaces::default();
+        interfaces.set(
+            "enp5s0",
+            vec![
+                "fe80::1".parse().unwrap(),
+                "192.168.1.23".parse().unwrap(),
+                "2001:db8::23".parse().unwrap(),
+            ],
+        );
+        let config = NetworkConfig {
+            format: "{ifname} {ipv4} {ipv6} via {gateway} mtu {mtu} {down}/s {up:KiB}".to_string(),
+            ..NetworkConfig::default()
+        };
+        let module = network(&config, &fs, &interfaces);
+        let render = |seconds| module.render(module.sample(Local.timestamp_opt(seconds, 0).unwrap()).as_ref());
+
+        // Nothing to compare the first counters with
+        assert_eq!(render(100), "enp5s0 192.168.1.23 2001:db8::23 via 192.168.1.1 mtu 1500 0B/s 0.0");
+
+        fs.write("class/net/enp5s0/statistics/rx_bytes", "11485760\n")
+            .write("class/net/enp5s0/statistics/tx_bytes", "25480\n");
+        // Within a second the previous reading stands
+        assert!(render(100).ends_with("0B/s 0.0"));
+        assert!(render(105).ends_with("2.0MiB/s 4.0"));
+    }
+
+    #[test]
+    fn network_thresholds_apply_per_interface() {
+        let fs = Fixture::new();
+        fs.write("class/net/wg0/operstate", "up\n")
+            .write("class/net/eth0/operstate", "up\n")
+            .write("class/net/eth0/statistics/rx_bytes", "0\n");
+        let config = Config::from_toml(
+            r#"
+            [module_config.network]
+            format_disconnected = "offline"
+            show_icon = false
+            warning_threshold = "1MiB"
+            critical_threshold = 5000000
+
+            [[module_config.network.interfaces]]
+            name = "wg0"
+            format = "VPN {ifname}"
+            critical_threshold = "2MB"
+            "#,
+        )
+        .unwrap()
+        .module_config
+        .network;
+        let module = network(&config, &fs, &FixedInterfaces::default());
+        let at = |seconds| Local.timestamp_opt(seconds, 0).unwrap();
+        let reading = |interface: &str, down| NetworkReading {
+            interface: interface.to_string(),
+            down,
+            ..NetworkReading::default()
+        };
+
+        // The configured interface wins over the ones found up
+        assert_eq!(module.render(module.sample(at(0)).as_ref()), "VPN wg0");
+        fs.write("class/net/wg0/operstate", "down\n");
+        assert_eq!(module.render(module.sample(at(10)).as_ref()), "eth0");
+        fs.write("class/net/eth0/operstate", "down\n");
+        assert_eq!(module.render(module.sample(at(20)).as_ref()), "offline");
+
+        assert_eq!(module.state(Some(&reading("eth0", 1e6))), ModuleState::Normal);
+        assert_eq!(module.state(Some(&reading("eth0", 2e6))), ModuleState::Warning);
+        assert_eq!(module.state(Some(&reading("eth0", 5e6))), ModuleState::Critical);
+        assert_eq!(module.state(Some(&reading("wg0", 2e6))), ModuleState::Critical);
+        assert_eq!(module.state(None), ModuleState::Normal);
+    }
+
+    fn window(title: &str, app_id: &str) -> Window {
+        Window {
+            id: 1,
+            title: Some(title.to_string()),
+            app_id: Some(app_id.to_string()),
+            workspace_id: Some(1),
+            is_focused: true,
+        }
+    }
+
+    fn rule(app_id: Option<&str>, pattern: &str, replacement: &str) -> RewriteRule {
+        RewriteRule {
+            app_id: app_id.map(str::to_string),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn window_formats_title_and_app_id() {
+        let config = WindowConfig {
+            format: "{app_id}: {title}".into(),
+            ..WindowConfig::default()
+        };
+        let module = WindowModule::new(&config);
+
+        assert_eq!(module.render(Some(&window("notes.md", "Helix"))), "Helix: notes.md");
+    }
+
+    #[test]
+    fn rewrite_rules_apply_per_app_id() {
+        let config = WindowConfig {
+            rewrite: vec![
+                rule(Some("^firefox$"), " — Mozilla Firefox$", ""),
+                rule(None, r"^(.*) - (\w+)$", "$2: $1"),
+                // Invalid patterns are skipped rather than failing the bar
+                rule(None, "(", ""),
+            ],
+            ..WindowConfig::default()
+        };
+        let module = WindowModule::new(&config);
+
+        assert_eq!(module.render(Some(&window("Docs — Mozilla Firefox", "firefox"))), "Docs");
+        assert_eq!(
+            module.render(Some(&window("Docs — Mozilla Firefox", "librewolf"))),
+            "Docs — Mozilla Firefox"
+        );
+        assert_eq!(module.render(Some(&window("main.rs - Code", "code"))), "Code: main.rs");
+    }
+
+    #[test]
+    fn long_titles_are_cut_and_empty_focus_shows_placeholder() {
+        let config = WindowConfig {
+            max_length: 8,
+            empty: "desktop".into(),
+            ..WindowConfig::default()
+        };
+        let module = WindowModule::new(&config);
+
+        assert_eq!(module.render(Some(&window("A very long title", "x"))), "A very …");
+        assert_eq!(module.render(Some(&window("Short", "x"))), "Short");
+        assert_eq!(module.render(None), "desktop");
+    }
+
+    fn on_workspace(id: u64, app_id: &str, workspace_id: u64, is_focused: bool) -> Window {
+        Window {
+            id,
+            workspace_id: Some(workspace_id),
+            is_focused,
+            ..window("title", app_id)
+        }
+    }
+
+    fn workspace(id: u64, idx: u32, is_focused: bool) -> Workspace {
+        Workspace {
+            id,
+            idx,
+            name: None,
+            output: Some("DP-1".into()),
+            is_urgent: false,
+            is_active: is_focused,
+            is_focused,
+        }
+    }
+
+    #[test]
+    fn taskbar_lists_the_focused_workspace() {
+        let module = TaskbarModule::new(&TaskbarConfig::default());
+        let windows = [
+            on_workspace(9, "org.gnome.Nautilus", 1, false),
+            on_workspace(4, "firefox", 1, true),
+            on_workspace(5, "Alacritty", 2, false),
+        ];
+        let workspaces = [workspace(1, 1, true), workspace(2, 2, false)];
+
+        let (text, entries) = module.render(&windows, &workspaces);
+        assert_eq!(text, "[fir] nau");
+        assert_eq!(
+            entries,
+            [
+                TaskbarEntry { window_id: 4, graphemes: 0..5 },
+                TaskbarEntry { window_id: 9, graphemes: 6..9 },
+            ]
+        );
+    }
+
+    #[test]
+    fn taskbar_can_span_all_workspaces_with_custom_labels() {
+        let config = TaskbarConfig {
+            scope: TaskbarScope::All,
+            label: TaskbarLabel::AppId,
+            icons: [("firefox".to_string(), "FF".to_string())].into(),
+            ..TaskbarConfig::default()
+        };
+        let module = TaskbarModule::new(&config);
+        let windows = [
+            on_workspace(1, "Alacritty", 10, false),
+            on_workspace(2, "firefox", 20, false),
+            on_workspace(3, "", 10, false),
+        ];
+        let workspaces = [workspace(10, 2, false), workspace(20, 1, true)];
+
+        // Workspace 20 has idx 1, so its window comes first
+        let (text, _) = module.render(&windows, &workspaces);
+        assert_eq!(text, "FF Alacritty ?");
+    }
+
+    #[test]
+    fn taskbar_shows_every_window_without_workspaces() {
+        let module = TaskbarModule::new(&TaskbarConfig::default());
+        let unplaced = |id, app_id, is_focused| Window {
+            id,
+            workspace_id: None,
+            is_focused,
+            ..window("title", app_id)
+        };
+        let windows = [unplaced(2, "foot", false), unplaced(1, "firefox", true)];
+
+        let (text, _) = module.render(&windows, &[]);
+        assert_eq!(text, "[fir] foo");
+    }
+
+    #[test]
+    fn keyboard_layout_maps_names_to_labels() {
+        let mut config = KeyboardLayoutConfig::default();
+        config.labels.insert("English (US)".into(), "us".into());
+        let module = KeyboardLayoutModule::new(&config);
+        let layouts = |current_idx| KeyboardLayouts {
+            names: vec!["English (US)".into(), "German".into()],
+            current_idx,
+        };
+        
+        assert_eq!(module.render(&layouts(0)), "us");
+        // Unmapped layouts keep their name
+        assert_eq!(module.render(&layouts(1)), "German");
+        assert_eq!(module.render(&layouts(7)), "");
+        
+        config.format = "{label} ({name})".into();
+        assert_eq!(KeyboardLayoutModule::new(&config).render(&layouts(0)), "us (English (US))");
+    }
 }
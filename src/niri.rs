@@ -1,111 +1,769 @@
-use serde::Deserialize;
-use std::io::{BufRead, BufReader, Write};
+use crate::compositor::{self, Compositor, WorkspaceEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::env;
 
-#[derive(Debug, Deserialize)]
-pub struct NiriWorkspace {
+// niri release these types were written against, for error messages
+pub const PROTOCOL_VERSION: &str = "25.02";
+// Oldest niri with the event stream and every request below
+const MIN_VERSION: [u32; 3] = [0, 1, 9];
+
+/// Requests, serialized the way niri expects: `"Workspaces"` or
+/// `{"Action":{...}}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Request {
+    Version,
+    Outputs,
+    Workspaces,
+    Windows,
+    FocusedWindow,
+    FocusedOutput,
+    KeyboardLayouts,
+    Action(Action),
+    EventStream,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Action {
+    FocusWorkspace { reference: WorkspaceReference },
+    FocusWindow { id: u64 },
+    CloseWindow { id: Option<u64> },
+    SwitchLayout { layout: LayoutSwitchTarget },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum WorkspaceReference {
+    Id(u64),
+    Index(u8),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum LayoutSwitchTarget {
+    Next,
+    Prev,
+    Index(u8),
+}
+
+/// The payload of a successful reply
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Response {
+    Handled,
+    Version(String),
+    Outputs(HashMap<String, Output>),
+    Workspaces(Vec<Workspace>),
+    Windows(Vec<Window>),
+    FocusedWindow(Option<Window>),
+    FocusedOutput(Option<Output>),
+    KeyboardLayouts(KeyboardLayouts),
+}
+
+// Every reply is either {"Ok": response} or {"Err": message}
+#[derive(Deserialize)]
+enum Reply {
+    Ok(Response),
+    Err(String),
+}
+
+/// What niri reports on its event stream. The first events on a new stream
+/// describe the full state; later ones what changed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Event {
+    WorkspacesChanged { workspaces: Vec<Workspace> },
+    WorkspaceUrgencyChanged { id: u64, urgent: bool },
+    WorkspaceActivated { id: u64, focused: bool },
+    WorkspaceActiveWindowChanged { workspace_id: u64, active_window_id: Option<u64> },
+    WindowsChanged { windows: Vec<Window> },
+    WindowOpenedOrChanged { window: Window },
+    WindowClosed { id: u64 },
+    WindowFocusChanged { id: Option<u64> },
+    KeyboardLayoutsChanged { keyboard_layouts: KeyboardLayouts },
+    KeyboardLayoutSwitched { idx: u8 },
+    /// An event these types don't know, by name, e.g. from a newer niri
+    #[serde(skip)]
+    Other(String),
+}
+
+impl Event {
+    fn decode(line: &str) -> Self {
+        serde_json::from_str(line).unwrap_or_else(|_| {
+            let name = serde_json::from_str::<HashMap<String, serde_json::Value>>(line)
+                .ok()
+                .and_then(|event| event.into_keys().next());
+            Event::Other(name.unwrap_or_default())
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Workspace {
     pub id: u64,
-    pub idx: u64,
-    #[allow(dead_code)]
+    pub idx: u8,
     pub name: Option<String>,
-    #[allow(dead_code)]
-    pub output: String,
-    #[allow(dead_code)]
+    pub output: Option<String>,
+    #[serde(default)]
     pub is_urgent: bool,
-    #[allow(dead_code)]
     pub is_active: bool,
     pub is_focused: bool,
-    #[allow(dead_code)]
     pub active_window_id: Option<u64>,
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Window {
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub pid: Option<i32>,
+    pub workspace_id: Option<u64>,
+    pub is_focused: bool,
+    #[serde(default)]
+    pub is_floating: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Output {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    pub logical: Option<LogicalOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LogicalOutput {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct KeyboardLayouts {
+    pub names: Vec<String>,
+    pub current_idx: u8,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// niri isn't reachable
+    Io(io::Error),
+    /// niri understood the request and refused it
+    Niri(String),
+    /// The reply didn't match the types above, most likely a protocol change
+    Protocol { version: Option<String>, detail: String },
+    /// niri is older than the oldest release we know how to talk to
+    Unsupported { version: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "can't reach niri: {}", e),
+            Error::Niri(message) => write!(f, "niri: {}", message),
+            Error::Protocol { version, detail } => write!(
+                f,
+                "niri {} replied in a format oxidebar doesn't understand \
+                 (built for niri {}): {}",
+                version.as_deref().unwrap_or("(unknown version)"),
+                PROTOCOL_VERSION,
+                detail
+            ),
+            Error::Unsupported { version } => write!(
+                f,
+                "niri {} is too old, oxidebar needs {} or newer",
+                version,
+                MIN_VERSION.map(|n| n.to_string()).join(".")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// How requests reach niri. Each call is one line of JSON out; tests swap in
+/// a transport that answers from a script.
+pub trait Transport: Send + Sync {
+    /// Send `request` and return the single reply line
+    fn request(&self, request: &str) -> io::Result<String>;
+
+    /// Send `request` and return every line niri writes back, starting with
+    /// the reply, until the connection closes
+    fn stream(&self, request: &str) -> io::Result<Box<dyn BufRead + Send>>;
+}
+
+/// niri's IPC socket, one connection per request
+pub struct SocketTransport {
+    path: PathBuf,
+}
+
+impl SocketTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn connect(&self, request: &str) -> io::Result<UnixStream> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        writeln!(stream, "{}", request)?;
+        stream.flush()?;
+        Ok(stream)
+    }
+}
+
+impl Transport for SocketTransport {
+    fn request(&self, request: &str) -> io::Result<String> {
+        let stream = self.connect(request)?;
+        stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+        Ok(response)
+    }
+
+    fn stream(&self, request: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(BufReader::new(self.connect(request)?)))
+    }
+}
+
 pub struct NiriIpc {
-    socket_path: String,
+    transport: Arc<dyn Transport>,
+    // niri's version, asked for before the first real request and again
+    // after anything suggests niri may have restarted
+    version: Arc<Mutex<Option<String>>>,
+    // Keyboard layouts as the event stream last described them, while it runs
+    keyboard_layouts: Arc<Mutex<Option<KeyboardLayouts>>>,
+    // Last error logged, so a persistent failure is reported once
+    last_error: Mutex<Option<String>>,
 }
 
 impl NiriIpc {
+    /// Connect to the niri instance named by NIRI_SOCKET, if any
     pub fn new() -> Option<Self> {
         let socket_path = env::var("NIRI_SOCKET").ok()?;
-        Some(Self { socket_path })
-    }
-
-    fn send_request(&self, request: &str) -> Result<String, std::io::Error> {
-        let stream = UnixStream::connect(&self.socket_path)?;
-        stream.set_read_timeout(Some(std::time::Duration::from_millis(500)))?;
-        
-        let mut stream_write = stream.try_clone()?;
-        let mut reader = BufReader::new(stream);
-        
-        let json_request = format!("\"{}\"", request);
-        writeln!(stream_write, "{}", json_request)?;
-        stream_write.flush()?;
-        
-        let mut response = String::new();
-        reader.read_line(&mut response)?;
-        
-        Ok(response)
+        Some(Self::with_transport(SocketTransport::new(socket_path)))
+    }
+
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            version: Arc::new(Mutex::new(None)),
+            keyboard_layouts: Arc::new(Mutex::new(None)),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Send `request` and decode the reply, checking niri's version first
+    pub fn request(&self, request: Request) -> Result<Response, Error> {
+        if request != Request::Version {
+            self.negotiate()?;
+        }
+        let response = self.send(&request);
+        if let Err(Error::Io(_) | Error::Protocol { .. }) = response {
+            // niri may have gone away and come back as another release
+            self.version.lock().unwrap().take();
+        }
+        response
+    }
+
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        let line = serde_json::to_string(request).expect("requests always serialize");
+        let reply = self.transport.request(&line)?;
+
+        match serde_json::from_str::<Reply>(&reply) {
+            Ok(Reply::Ok(response)) => Ok(response),
+            Ok(Reply::Err(message)) => Err(Error::Niri(message)),
+            Err(e) => Err(self.protocol_error(e.to_string())),
+        }
+    }
+
+    // Ask niri for its version unless we know it and refuse to talk to
+    // releases that predate the protocol we speak
+    fn negotiate(&self) -> Result<(), Error> {
+        if self.version.lock().unwrap().is_some() {
+            return Ok(());
+        }
+
+        let version = match self.send(&Request::Version)? {
+            Response::Version(version) => version,
+            other => return Err(self.unexpected("Version", &other)),
+        };
+        if parse_version(&version).is_some_and(|v| v < MIN_VERSION) {
+            return Err(Error::Unsupported { version });
+        }
+        *self.version.lock().unwrap() = Some(version);
+        Ok(())
+    }
+
+    fn protocol_error(&self, detail: String) -> Error {
+        Error::Protocol {
+            version: self.version.lock().unwrap().clone(),
+            detail,
+        }
+    }
+
+    fn unexpected(&self, expected: &str, response: &Response) -> Error {
+        self.protocol_error(format!("expected {} in reply, got {:?}", expected, response))
+    }
+
+    pub fn version(&self) -> Result<String, Error> {
+        match self.request(Request::Version)? {
+            Response::Version(version) => Ok(version),
+            other => Err(self.unexpected("Version", &other)),
+        }
+    }
+
+    pub fn outputs(&self) -> Result<HashMap<String, Output>, Error> {
+        match self.request(Request::Outputs)? {
+            Response::Outputs(outputs) => Ok(outputs),
+            other => Err(self.unexpected("Outputs", &other)),
+        }
+    }
+
+    pub fn workspaces(&self) -> Result<Vec<Workspace>, Error> {
+        match self.request(Request::Workspaces)? {
+            Response::Workspaces(workspaces) => Ok(workspaces),
+            other => Err(self.unexpected("Workspaces", &other)),
+        }
+    }
+
+    pub fn windows(&self) -> Result<Vec<Window>, Error> {
+        match self.request(Request::Windows)? {
+            Response::Windows(windows) => Ok(windows),
+            other => Err(self.unexpected("Windows", &other)),
+        }
+    }
+
+    pub fn focused_window(&self) -> Result<Option<Window>, Error> {
+        match self.request(Request::FocusedWindow)? {
+            Response::FocusedWindow(window) => Ok(window),
+            other => Err(self.unexpected("FocusedWindow", &other)),
+        }
+    }
+
+    pub fn focused_output(&self) -> Result<Option<Output>, Error> {
+        match self.request(Request::FocusedOutput)? {
+            Response::FocusedOutput(output) => Ok(output),
+            other => Err(self.unexpected("FocusedOutput", &other)),
+        }
+    }
+
+    pub fn keyboard_layouts(&self) -> Result<KeyboardLayouts, Error> {
+        match self.request(Request::KeyboardLayouts)? {
+            Response::KeyboardLayouts(layouts) => Ok(layouts),
+            other => Err(self.unexpected("KeyboardLayouts", &other)),
+        }
+    }
+
+    pub fn action(&self, action: Action) -> Result<(), Error> {
+        match self.request(Request::Action(action))? {
+            Response::Handled => Ok(()),
+            other => Err(self.unexpected("Handled", &other)),
+        }
+    }
+
+    /// Follow niri's event stream on a background thread, calling `on_event`
+    /// with every event until it returns false. Reconnects if niri goes
+    /// away, calling `on_event` with None in between since anything may have
+    /// changed meanwhile.
+    pub fn spawn_event_stream<F>(&self, on_event: F)
+    where
+        F: FnMut(Option<Event>) -> bool + Send + 'static,
+    {
+        let transport = self.transport.clone();
+        let version = self.version.clone();
+        compositor::spawn_reconnecting(
+            move |on_event: &mut F| Self::follow_events(transport.as_ref(), on_event),
+            on_event,
+            move |on_event| {
+                version.lock().unwrap().take();
+                on_event(None)
+            },
+        );
     }
 
-    pub fn get_workspaces(&self) -> Option<Vec<NiriWorkspace>> {
-        let response = self.send_request("Workspaces").ok()?;
-        
-        #[derive(Deserialize)]
-        struct WorkspacesData {
-            #[serde(rename = "Workspaces")]
-            workspaces: Vec<NiriWorkspace>,
-        }
-        
-        #[derive(Deserialize)]
-        struct Response {
-            #[serde(rename = "Ok")]
-            ok: Option<WorkspacesData>,
-        }
-        
-        serde_json::from_str::<Response>(&response)
-            .ok()?
-            .ok
-            .map(|data| data.workspaces)
-    }
-
-    #[allow(dead_code)]
-    pub fn get_focused_workspace(&self) -> Option<String> {
-        let workspaces = self.get_workspaces()?;
-        
-        for ws in workspaces {
-            if ws.is_focused {
-                return Some(
-                    ws.name
-                        .unwrap_or_else(|| format!("{}", ws.id))
-                );
+    // Read events until the connection drops; false once `on_event` asks to stop
+    fn follow_events(transport: &dyn Transport, on_event: &mut dyn FnMut(Option<Event>) -> bool) -> bool {
+        let request = serde_json::to_string(&Request::EventStream).expect("requests always serialize");
+        let Ok(stream) = transport.stream(&request) else {
+            return true;
+        };
+
+        // The first line only acknowledges the request
+        for line in stream.lines().skip(1) {
+            let Ok(line) = line else {
+                return true;
+            };
+            if !on_event(Some(Event::decode(&line))) {
+                return false;
             }
         }
-        
-        None
+        true
     }
 
     pub fn get_workspace_summary(&self) -> String {
-        match self.get_workspaces() {
-            Some(mut workspaces) => {
-                if workspaces.is_empty() {
-                    return String::from("Empty");
+        self.summary().0
+    }
+
+    fn summary(&self) -> (String, Vec<WorkspaceEntry>) {
+        match self.workspaces() {
+            Ok(workspaces) => {
+                self.last_error.lock().unwrap().take();
+                compositor::summarize(workspaces.into_iter().map(Into::into).collect())
+            }
+            Err(e) => {
+                self.log_error(&e);
+                let text = match e {
+                    // Say why rather than looking like niri isn't running
+                    Error::Unsupported { version } => format!("niri {} unsupported", version),
+                    Error::Protocol { version: Some(version), .. } => {
+                        format!("niri {} unsupported", version)
+                    }
+                    _ => String::from("WS ?"),
+                };
+                (text, Vec::new())
+            }
+        }
+    }
+
+    fn log_error(&self, error: &Error) {
+        let message = error.to_string();
+        let mut last = self.last_error.lock().unwrap();
+        if last.as_deref() != Some(message.as_str()) {
+            eprintln!("{}", message);
+            *last = Some(message);
+        }
+    }
+}
+
+impl Compositor for NiriIpc {
+    fn workspaces(&self) -> anyhow::Result<Vec<compositor::Workspace>> {
+        Ok(NiriIpc::workspaces(self)?.into_iter().map(Into::into).collect())
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<compositor::Window>> {
+        Ok(NiriIpc::windows(self)?.into_iter().map(Into::into).collect())
+    }
+
+    fn focused_window(&self) -> anyhow::Result<Option<compositor::Window>> {
+        Ok(NiriIpc::focused_window(self)?.map(Into::into))
+    }
+
+    fn keyboard_layouts(&self) -> anyhow::Result<compositor::KeyboardLayouts> {
+        let known = self.keyboard_layouts.lock().unwrap().clone();
+        let layouts = match known {
+            Some(layouts) => layouts,
+            None => NiriIpc::keyboard_layouts(self)?,
+        };
+        Ok(compositor::KeyboardLayouts {
+            names: layouts.names,
+            current_idx: layouts.current_idx as usize,
+        })
+    }
+
+    fn action(&self, action: compositor::Action) -> anyhow::Result<()> {
+        let action = match action {
+            compositor::Action::FocusWorkspace { id } => Action::FocusWorkspace {
+                reference: WorkspaceReference::Id(id),
+            },
+            compositor::Action::DeactivateWorkspace { .. } => {
+                anyhow::bail!("niri can't deactivate workspaces")
+            }
+            compositor::Action::FocusWindow { id } => Action::FocusWindow { id },
+            compositor::Action::MinimizeWindow { .. } => anyhow::bail!("niri can't minimize windows"),
+            compositor::Action::CloseWindow { id } => Action::CloseWindow { id: Some(id) },
+            compositor::Action::NextLayout => Action::SwitchLayout { layout: LayoutSwitchTarget::Next },
+            compositor::Action::PrevLayout => Action::SwitchLayout { layout: LayoutSwitchTarget::Prev },
+        };
+        Ok(NiriIpc::action(self, action)?)
+    }
+
+    fn spawn_event_stream(&self, mut on_event: Box<dyn FnMut() -> bool + Send>) {
+        let keyboard_layouts = self.keyboard_layouts.clone();
+        NiriIpc::spawn_event_stream(self, move |event| {
+            let mut layouts = keyboard_layouts.lock().unwrap();
+            match event {
+                Some(Event::KeyboardLayoutsChanged { keyboard_layouts }) => *layouts = Some(keyboard_layouts),
+                Some(Event::KeyboardLayoutSwitched { idx }) => {
+                    if let Some(layouts) = layouts.as_mut() {
+                        layouts.current_idx = idx;
+                    }
                 }
-                
-                workspaces.sort_by_key(|w| w.idx);
-                
-                let occupied: Vec<String> = workspaces.iter()
-                    .map(|ws| {
-                        if ws.is_focused {
-                            format!("[{}]", ws.idx)
-                        } else {
-                            format!("{}", ws.idx)
-                        }
-                    })
-                    .collect();
-                
-                occupied.join(" ")
+                // Changes may have been missed; ask until the stream is back
+                None => *layouts = None,
+                Some(_) => {}
             }
-            None => String::from("WS ?"),
+            drop(layouts);
+            on_event()
+        })
+    }
+
+    fn workspace_summary(&self) -> (String, Vec<WorkspaceEntry>) {
+        self.summary()
+    }
+}
+
+impl From<Workspace> for compositor::Workspace {
+    fn from(ws: Workspace) -> Self {
+        Self {
+            id: ws.id,
+            idx: ws.idx.into(),
+            name: ws.name,
+            output: ws.output,
+            is_active: ws.is_active,
+            is_focused: ws.is_focused,
+            is_urgent: ws.is_urgent,
+        }
+    }
+}
+
+impl From<Window> for compositor::Window {
+    fn from(window: Window) -> Self {
+        Self {
+            id: window.id,
+            title: window.title,
+            app_id: window.app_id,
+            workspace_id: window.workspace_id,
+            is_focused: window.is_focused,
         }
     }
 }
+
+// "25.02 (abc1234)" or "0.1.10-1" -> [25, 2, 0] / [0, 1, 10]
+fn parse_version(version: &str) -> Option<[u32; 3]> {
+    let number = version.split([' ', '-']).next()?;
+    let mut parts = [0; 3];
+    for (part, value) in parts.iter_mut().zip(number.split('.')) {
+        *part = value.parse().ok()?;
+    }
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::mpsc;
+
+    // Answers requests from a fixed script
+    struct Scripted {
+        replies: Vec<(&'static str, String)>,
+    }
+
+    impl Transport for Scripted {
+        fn request(&self, request: &str) -> io::Result<String> {
+            let reply = self.replies.iter().find(|(req, _)| *req == request);
+            match reply {
+                Some((_, reply)) => Ok(format!("{}\n", reply)),
+                None if request == "\"Version\"" => {
+                    Ok(r#"{"Ok":{"Version":"25.02 (abc1234)"}}"#.to_string())
+                }
+                None => Err(io::ErrorKind::ConnectionRefused.into()),
+            }
+        }
+
+        fn stream(&self, request: &str) -> io::Result<Box<dyn BufRead + Send>> {
+            let lines = self.request(request)?;
+            Ok(Box::new(Cursor::new(lines.into_bytes())))
+        }
+    }
+
+    fn workspace(idx: u8, is_focused: bool) -> String {
+        format!(
+            r#"{{"id":{},"idx":{},"name":null,"output":"DP-1","is_urgent":false,"is_active":{},"is_focused":{},"active_window_id":null}}"#,
+            idx + 10, idx, is_focused, is_focused
+        )
+    }
+
+    #[test]
+    fn summarizes_workspaces_in_order() {
+        let reply = format!(
+            r#"{{"Ok":{{"Workspaces":[{},{},{}]}}}}"#,
+            workspace(3, false),
+            workspace(1, false),
+            workspace(2, true)
+        );
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![("\"Workspaces\"", reply)],
+        });
+
+        assert_eq!(niri.get_workspace_summary(), "1 [2] 3");
+        assert_eq!(niri.version().unwrap(), "25.02 (abc1234)");
+    }
+
+    #[test]
+    fn requests_serialize_like_niri_expects() {
+        let encode = |request: Request| serde_json::to_string(&request).unwrap();
+
+        assert_eq!(encode(Request::Workspaces), r#""Workspaces""#);
+        assert_eq!(
+            encode(Request::Action(Action::FocusWindow { id: 3 })),
+            r#"{"Action":{"FocusWindow":{"id":3}}}"#
+        );
+        assert_eq!(
+            encode(Request::Action(Action::CloseWindow { id: None })),
+            r#"{"Action":{"CloseWindow":{"id":null}}}"#
+        );
+        assert_eq!(
+            encode(Request::Action(Action::SwitchLayout { layout: LayoutSwitchTarget::Next })),
+            r#"{"Action":{"SwitchLayout":{"layout":"Next"}}}"#
+        );
+        assert_eq!(
+            encode(Request::Action(Action::FocusWorkspace {
+                reference: WorkspaceReference::Index(2)
+            })),
+            r#"{"Action":{"FocusWorkspace":{"reference":{"Index":2}}}}"#
+        );
+    }
+
+    #[test]
+    fn err_replies_are_propagated() {
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![("\"Windows\"", r#"{"Err":"no windows here"}"#.to_string())],
+        });
+
+        match niri.windows() {
+            Err(Error::Niri(message)) => assert_eq!(message, "no windows here"),
+            other => panic!("expected a niri error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn old_niri_is_refused_by_name() {
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![("\"Version\"", r#"{"Ok":{"Version":"0.1.8"}}"#.to_string())],
+        });
+
+        assert!(matches!(niri.workspaces(), Err(Error::Unsupported { .. })));
+        assert_eq!(niri.get_workspace_summary(), "niri 0.1.8 unsupported");
+    }
+
+    #[test]
+    fn unknown_reply_shapes_name_the_niri_version() {
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![("\"Workspaces\"", r#"{"Ok":{"Workspaces":{"list":[]}}}"#.to_string())],
+        });
+
+        let error = niri.workspaces().unwrap_err();
+        assert!(
+            error.to_string().starts_with("niri 25.02 (abc1234) replied in a format"),
+            "{}",
+            error
+        );
+        assert_eq!(niri.get_workspace_summary(), "niri 25.02 (abc1234) unsupported");
+    }
+
+    #[test]
+    fn versions_parse_both_numbering_schemes() {
+        assert_eq!(parse_version("25.02 (abc1234)"), Some([25, 2, 0]));
+        assert_eq!(parse_version("0.1.10-1"), Some([0, 1, 10]));
+        assert_eq!(parse_version("unstable"), None);
+    }
+
+    #[test]
+    fn unreachable_or_failing_niri_shows_unknown() {
+        let niri = NiriIpc::with_transport(Scripted { replies: vec![] });
+        assert_eq!(niri.get_workspace_summary(), "WS ?");
+
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![("\"Workspaces\"", r#"{"Err":"boom"}"#.to_string())],
+        });
+        assert_eq!(niri.get_workspace_summary(), "WS ?");
+    }
+
+    #[test]
+    fn event_stream_skips_the_reply_line() {
+        let niri = NiriIpc::with_transport(Scripted {
+            replies: vec![(
+                "\"EventStream\"",
+                [
+                    r#"{"Ok":"Handled"}"#,
+                    r#"{"WorkspaceActivated":{"id":1,"focused":true}}"#,
+                    r#"{"WindowFocusChanged":{"id":null}}"#,
+                ]
+                .join("\n"),
+            )],
+        });
+
+        // Two events, then None when the stream ends
+        let (sender, events) = mpsc::channel();
+        niri.spawn_event_stream(move |event| {
+            let more = event.is_some();
+            sender.send(event).is_ok() && more
+        });
+        let seen: Vec<_> = (0..3)
+            .map(|_| events.recv_timeout(Duration::from_secs(1)).unwrap())
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                Some(Event::WorkspaceActivated { id: 1, focused: true }),
+                Some(Event::WindowFocusChanged { id: None }),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn events_decode_to_their_payloads() {
+        assert_eq!(
+            Event::decode(r#"{"KeyboardLayoutSwitched":{"idx":1}}"#),
+            Event::KeyboardLayoutSwitched { idx: 1 }
+        );
+        assert_eq!(
+            Event::decode(r#"{"KeyboardLayoutsChanged":{"keyboard_layouts":{"names":["us"],"current_idx":0}}}"#),
+            Event::KeyboardLayoutsChanged {
+                keyboard_layouts: KeyboardLayouts { names: vec!["us".into()], current_idx: 0 },
+            }
+        );
+        assert_eq!(
+            Event::decode(r#"{"OverviewOpenedOrClosed":{"is_open":true}}"#),
+            Event::Other("OverviewOpenedOrClosed".into())
+        );
+    }
+
+    #[test]
+    fn failed_requests_check_the_version_again() {
+        // Records requests; Windows works, Workspaces can't get through
+        struct Flaky {
+            requests: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl Transport for Flaky {
+            fn request(&self, request: &str) -> io::Result<String> {
+                self.requests.lock().unwrap().push(request.to_string());
+                match request {
+                    "\"Version\"" => Ok(r#"{"Ok":{"Version":"25.02"}}"#.to_string()),
+                    "\"Windows\"" => Ok(r#"{"Ok":{"Windows":[]}}"#.to_string()),
+                    _ => Err(io::ErrorKind::ConnectionReset.into()),
+                }
+            }
+
+            fn stream(&self, _: &str) -> io::Result<Box<dyn BufRead + Send>> {
+                Err(io::ErrorKind::ConnectionRefused.into())
+            }
+        }
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let niri = NiriIpc::with_transport(Flaky { requests: requests.clone() });
+        niri.windows().unwrap();
+        niri.windows().unwrap();
+        assert!(niri.workspaces().is_err());
+        niri.windows().unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            ["\"Version\"", "\"Windows\"", "\"Windows\"", "\"Workspaces\"", "\"Version\"", "\"Windows\""]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+// The compositor as seen through standard Wayland protocols, for when no IPC
+// socket is around: workspaces from ext-workspace-v1 and windows from
+// wlr-foreign-toplevel-management, whichever of them the compositor offers.
+
+use crate::ext_workspace::ExtWorkspaces;
+use crate::foreign_toplevel::ForeignToplevels;
+use anyhow::bail;
+use oxidebar::compositor::{Action, Compositor, Window, Workspace};
+use std::sync::{Arc, Mutex};
+
+pub struct ProtocolCompositor {
+    workspaces: Option<ExtWorkspaces>,
+    toplevels: Option<ForeignToplevels>,
+}
+
+impl ProtocolCompositor {
+    /// None when the compositor offers neither protocol
+    pub fn new(workspaces: Option<ExtWorkspaces>, toplevels: Option<ForeignToplevels>) -> Option<Self> {
+        if workspaces.is_none() && toplevels.is_none() {
+            return None;
+        }
+        Some(Self { workspaces, toplevels })
+    }
+
+    // The protocol that carries out `action`, if the compositor offers it
+    fn handler(&self, action: &Action) -> Option<&dyn Compositor> {
+        match action {
+            Action::FocusWorkspace { .. } | Action::DeactivateWorkspace { .. } => {
+                self.workspaces.as_ref().map(|w| w as _)
+            }
+            _ => self.toplevels.as_ref().map(|t| t as _),
+        }
+    }
+}
+
+impl Compositor for ProtocolCompositor {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>> {
+        match &self.workspaces {
+            Some(workspaces) => workspaces.workspaces(),
+            None => bail!("the compositor doesn't offer ext-workspace-v1"),
+        }
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>> {
+        match &self.toplevels {
+            Some(toplevels) => toplevels.windows(),
+            None => bail!("the compositor doesn't offer wlr-foreign-toplevel-management"),
+        }
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()> {
+        match self.handler(&action) {
+            Some(compositor) => compositor.action(action),
+            None => bail!("the compositor offers no protocol for {:?}", action),
+        }
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        self.handler(action).is_some_and(|compositor| compositor.supports(action))
+    }
+
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>) {
+        // Both protocols report to the same callback
+        let on_event = Arc::new(Mutex::new(on_event));
+        let sources = [
+            self.workspaces.as_ref().map(|w| w as &dyn Compositor),
+            self.toplevels.as_ref().map(|t| t as &dyn Compositor),
+        ];
+        for source in sources.into_iter().flatten() {
+            let on_event = on_event.clone();
+            source.spawn_event_stream(Box::new(move || (on_event.lock().unwrap())()));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// Where modules get their data from
+//
+// Everything that touches the machine - sysfs, procfs, the clock, compositor
+// IPC - is reached through here so tests can point the bar at fixtures instead.
+
+use crate::compositor::{self, Compositor};
+use chrono::{DateTime, Local, TimeZone};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub trait Clock: Send {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The real wall clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep one and hand the other to the bar.
+#[derive(Clone)]
+pub struct ManualClock(Arc<Mutex<DateTime<Local>>>);
+
+impl ManualClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.0.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Space on one filesystem, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DiskUsage {
+    pub total: u64,
+    pub free: u64,
+    // Free to unprivileged users, i.e. less the root reserve
+    pub available: u64,
+}
+
+pub trait Filesystems: Send {
+    /// Usage of the filesystem `path` is on, None if it can't be queried
+    fn usage(&self, path: &Path) -> Option<DiskUsage>;
+}
+
+/// The mounted filesystems, via statvfs(3)
+pub struct Statvfs;
+
+impl Filesystems for Statvfs {
+    fn usage(&self, path: &Path) -> Option<DiskUsage> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        // SAFETY: statvfs only writes to `stat`, and `path` is NUL terminated
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        let block = stat.f_frsize as u64;
+        Some(DiskUsage {
+            total: stat.f_blocks as u64 * block,
+            free: stat.f_bfree as u64 * block,
+            available: stat.f_bavail as u64 * block,
+        })
+    }
+}
+
+/// Fixed usage by path, for tests. Clones share the same table.
+#[derive(Clone, Default)]
+pub struct FixedFilesystems(Arc<Mutex<HashMap<PathBuf, DiskUsage>>>);
+
+impl FixedFilesystems {
+    pub fn set(&self, path: impl Into<PathBuf>, usage: DiskUsage) {
+        self.0.lock().unwrap().insert(path.into(), usage);
+    }
+}
+
+impl Filesystems for FixedFilesystems {
+    fn usage(&self, path: &Path) -> Option<DiskUsage> {
+        self.0.lock().unwrap().get(path).copied()
+    }
+}
+
+pub trait Interfaces: Send {
+    /// IPv4 and IPv6 addresses assigned to network interface `name`
+    fn addresses(&self, name: &str) -> Vec<IpAddr>;
+}
+
+/// The network interfaces, via getifaddrs(3)
+pub struct Getifaddrs;
+
+impl Interfaces for Getifaddrs {
+    fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+        // SAFETY: the list stays valid until freeifaddrs, and each address
+        // is only read as the sockaddr type its family says it is
+        if unsafe { libc::getifaddrs(&mut list) } != 0 {
+            return Vec::new();
+        }
+        let mut addresses = Vec::new();
+        let mut entry = list;
+        while let Some(ifaddr) = unsafe { entry.as_ref() } {
+            entry = ifaddr.ifa_next;
+            let address = ifaddr.ifa_addr;
+            if address.is_null() || unsafe { CStr::from_ptr(ifaddr.ifa_name) }.to_bytes() != name.as_bytes() {
+                continue;
+            }
+            match i32::from(unsafe { (*address).sa_family }) {
+                libc::AF_INET => {
+                    let address = unsafe { &*(address as *const libc::sockaddr_in) };
+                    addresses.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr))));
+                }
+                libc::AF_INET6 => {
+                    let address = unsafe { &*(address as *const libc::sockaddr_in6) };
+                    addresses.push(IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr)));
+                }
+                _ => {}
+            }
+        }
+        unsafe { libc::freeifaddrs(list) };
+        addresses
+    }
+}
+
+/// Fixed addresses by interface, for tests. Clones share the same table.
+#[derive(Clone, Default)]
+pub struct FixedInterfaces(Arc<Mutex<HashMap<String, Vec<IpAddr>>>>);
+
+impl FixedInterfaces {
+    pub fn set(&self, name: &str, addresses: Vec<IpAddr>) {
+        self.0.lock().unwrap().insert(name.to_string(), addresses);
+    }
+}
+
+impl Interfaces for FixedInterfaces {
+    fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        self.0.lock().unwrap().get(name).cloned().unwrap_or_default()
+    }
+}
+
+pub struct Sources {
+    /// Where sysfs is mounted
+    pub sysfs: PathBuf,
+    /// Where procfs is mounted
+    pub procfs: PathBuf,
+    pub clock: Box<dyn Clock>,
+    pub filesystems: Box<dyn Filesystems>,
+    pub interfaces: Box<dyn Interfaces>,
+    pub compositor: Option<Box<dyn Compositor>>,
+}
+
+impl Sources {
+    /// The running system: /sys, /proc, the wall clock, the mounted
+    /// filesystems, the network interfaces and whichever compositor the
+    /// environment points at
+    pub fn system() -> Self {
+        Self {
+            sysfs: PathBuf::from("/sys"),
+            procfs: PathBuf::from("/proc"),
+            clock: Box::new(SystemClock),
+            filesystems: Box::new(Statvfs),
+            interfaces: Box::new(Getifaddrs),
+            compositor: compositor::detect(),
+        }
+    }
+
+    /// Nothing from the machine: no sysfs or procfs, no filesystems or
+    /// network interfaces, no compositor, and a clock stopped at noon on
+    /// 2024-01-01, so the bar draws the same everywhere
+    pub fn fixed() -> Self {
+        let noon = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).earliest().unwrap_or_else(Local::now);
+        Self {
+            sysfs: PathBuf::from("/nonexistent/oxidebar/sys"),
+            procfs: PathBuf::from("/nonexistent/oxidebar/proc"),
+            clock: Box::new(ManualClock::new(noon)),
+            filesystems: Box::new(FixedFilesystems::default()),
+            interfaces: Box::new(FixedInterfaces::default()),
+            compositor: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statvfs_reports_the_filesystem_of_a_path() {
+        let usage = Statvfs.usage(&std::env::temp_dir()).unwrap();
+        assert!(usage.total > 0);
+        assert!(usage.available <= usage.free && usage.free <= usage.total);
+
+        assert_eq!(Statvfs.usage(Path::new("/nonexistent/oxidebar")), None);
+    }
+
+    #[test]
+    fn getifaddrs_lists_the_addresses_of_an_interface() {
+        assert!(Getifaddrs.addresses("lo").contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert!(Getifaddrs.addresses("nonexistent0").is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+// Small swapchain on top of sctk's SlotPool
+//
+// The compositor may keep reading a buffer until it sends a release, so each
+// frame is drawn into whichever buffer has been released. A new buffer is only
+// allocated when all existing ones are still held, up to MAX_BUFFERS.
+
+use smithay_client_toolkit::shm::{
+    slot::{Buffer, CreateBufferError, SlotPool},
+    CreatePoolError, Shm,
+};
+use wayland_client::protocol::wl_shm;
+
+const MAX_BUFFERS: usize = 3;
+
+pub struct Swapchain {
+    pool: SlotPool,
+    buffers: Vec<Buffer>,
+    width: u32,
+    height: u32,
+}
+
+impl Swapchain {
+    pub fn new(shm: &Shm, width: u32, height: u32) -> Result<Self, CreatePoolError> {
+        // Room for double buffering up front; the pool grows if a third is needed
+        let pool = SlotPool::new(2 * buffer_len(width, height).max(1), shm)?;
+        Ok(Self {
+            pool,
+            buffers: Vec::new(),
+            width,
+            height,
+        })
+    }
+
+    /// Drop buffers of the old size; ones the compositor still holds are
+    /// destroyed once it releases them
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.buffers.clear();
+        self.width = width;
+        self.height = height;
+
+        let needed = 2 * buffer_len(width, height);
+        if needed > self.pool.len() {
+            if let Err(e) = self.pool.resize(needed) {
+                eprintln!("Failed to grow buffer pool: {}", e);
+            }
+        }
+    }
+
+    /// A buffer the compositor is not reading from, along with its pixels.
+    /// `None` when every buffer is still in use.
+    pub fn acquire(&mut self) -> Result<Option<(&Buffer, &mut [u8])>, CreateBufferError> {
+        let pool = &mut self.pool;
+        let free = self.buffers.iter().position(|b| b.canvas(pool).is_some());
+
+        let index = match free {
+            Some(index) => index,
+            None if self.buffers.len() < MAX_BUFFERS => {
+                let (buffer, _) = self.pool.create_buffer(
+                    self.width as i32,
+                    self.height as i32,
+                    self.width as i32 * 4,
+                    wl_shm::Format::Argb8888,
+                )?;
+                self.buffers.push(buffer);
+                self.buffers.len() - 1
+            }
+            None => return Ok(None),
+        };
+
+        let buffer = &self.buffers[index];
+        Ok(buffer.canvas(&mut self.pool).map(|canvas| (buffer, canvas)))
+    }
+}
+
+fn buffer_len(width: u32, height: u32) -> usize {
+    (width * height * 4) as usize
+}
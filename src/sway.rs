@@ -0,0 +1,356 @@
+// sway's i3-ipc over the socket in SWAYSOCK
+//
+// Every message is the magic string "i3-ipc", the payload length and the
+// message type as native-endian u32s, then a JSON payload. Replies carry the
+// type of their request; events have the high bit set.
+
+use crate::compositor::{self, Action, Compositor, KeyboardLayouts, Window, Workspace};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_INPUTS: u32 = 100;
+
+// Events that can change what the bar shows
+const EVENTS: &str = r#"["workspace","window","input"]"#;
+
+// How long sway may take to answer before it counts as unreachable
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+// Where sway keeps scratchpad windows; they aren't on any real workspace
+const SCRATCHPAD: &str = "__i3_scratch";
+
+#[derive(Debug, Deserialize)]
+struct SwayWorkspace {
+    id: u64,
+    num: i32,
+    name: String,
+    output: String,
+    visible: bool,
+    focused: bool,
+    #[serde(default)]
+    urgent: bool,
+}
+
+// A container in GET_TREE's reply; windows are its leaves
+#[derive(Debug, Deserialize)]
+struct Node {
+    id: u64,
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    #[serde(default)]
+    focused: bool,
+    // Native Wayland windows
+    app_id: Option<String>,
+    // Xwayland windows
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    floating_nodes: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandResult {
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Input {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    xkb_layout_names: Vec<String>,
+    xkb_active_layout_index: Option<usize>,
+}
+
+pub struct SwayIpc {
+    path: PathBuf,
+}
+
+impl SwayIpc {
+    /// Connect to the sway instance named by SWAYSOCK, if any
+    pub fn new() -> Option<Self> {
+        let socket_path = env::var("SWAYSOCK").ok()?;
+        Some(Self::with_socket(socket_path))
+    }
+
+    pub fn with_socket(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn connect(&self) -> anyhow::Result<UnixStream> {
+        let stream = UnixStream::connect(&self.path).context("can't reach sway")?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        Ok(stream)
+    }
+
+    // Send one message and decode the reply as `T`
+    fn request<T: for<'de> Deserialize<'de>>(&self, kind: u32, payload: &str) -> anyhow::Result<T> {
+        let mut stream = self.connect()?;
+        write_message(&mut stream, kind, payload.as_bytes())?;
+        let (reply_kind, reply) = read_message(&mut stream)?;
+        if reply_kind != kind {
+            bail!("sway answered message {} with message {}", kind, reply_kind);
+        }
+        serde_json::from_slice(&reply).context("unexpected reply from sway")
+    }
+
+    /// Run sway commands, e.g. `[con_id=4] focus`
+    pub fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        let results: Vec<CommandResult> = self.request(RUN_COMMAND, command)?;
+        for result in results {
+            if !result.success {
+                bail!("sway: {}", result.error.unwrap_or_else(|| command.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // Read events until the connection drops; false once `on_event` asks to stop
+    fn follow_events(&self, on_event: &mut dyn FnMut() -> bool) -> bool {
+        let Ok(mut stream) = self.connect() else {
+            return true;
+        };
+        // Events come whenever they happen, however long that takes
+        if stream.set_read_timeout(None).is_err() {
+            return true;
+        }
+        if write_message(&mut stream, SUBSCRIBE, EVENTS.as_bytes()).is_err() {
+            return true;
+        }
+
+        // The first message only acknowledges the subscription
+        let mut acknowledged = false;
+        while let Ok((kind, _)) = read_message(&mut stream) {
+            if !acknowledged {
+                acknowledged = kind == SUBSCRIBE;
+                continue;
+            }
+            if !on_event() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Compositor for SwayIpc {
+    fn workspaces(&self) -> anyhow::Result<Vec<Workspace>> {
+        let workspaces: Vec<SwayWorkspace> = self.request(GET_WORKSPACES, "")?;
+        // Named workspaces have no number; they follow the numbered ones, in
+        // sway's order
+        let mut last = workspaces.iter().filter_map(|ws| u32::try_from(ws.num).ok()).max().unwrap_or(0);
+        Ok(workspaces
+            .into_iter()
+            .map(|ws| Workspace {
+                id: ws.id,
+                idx: u32::try_from(ws.num).unwrap_or_else(|_| {
+                    last += 1;
+                    last
+                }),
+                name: Some(ws.name),
+                output: Some(ws.output),
+                is_active: ws.visible,
+                is_focused: ws.focused,
+                is_urgent: ws.urgent,
+            })
+            .collect())
+    }
+
+    fn windows(&self) -> anyhow::Result<Vec<Window>> {
+        let tree: Node = self.request(GET_TREE, "")?;
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, &mut windows);
+        Ok(windows)
+    }
+
+    fn keyboard_layouts(&self) -> anyhow::Result<KeyboardLayouts> {
+        let inputs: Vec<Input> = self.request(GET_INPUTS, "")?;
+        let Some(keyboard) = inputs
+            .into_iter()
+            .find(|i| i.kind == "keyboard" && !i.xkb_layout_names.is_empty())
+        else {
+            bail!("sway reports no keyboard layouts");
+        };
+        Ok(KeyboardLayouts {
+            names: keyboard.xkb_layout_names,
+            current_idx: keyboard.xkb_active_layout_index.unwrap_or(0),
+        })
+    }
+
+    fn action(&self, action: Action) -> anyhow::Result<()> {
+        let command = match action {
+            Action::FocusWorkspace { id } => {
+                let workspaces: Vec<SwayWorkspace> = self.request(GET_WORKSPACES, "")?;
+                let Some(ws) = workspaces.into_iter().find(|ws| ws.id == id) else {
+                    bail!("sway: no workspace with id {}", id);
+                };
+                format!("workspace {}", quote(&ws.name))
+            }
+            Action::DeactivateWorkspace { .. } => bail!("sway can't deactivate workspaces"),
+            Action::FocusWindow { id } => format!("[con_id={}] focus", id),
+            Action::MinimizeWindow { .. } => bail!("sway can't minimize windows"),
+            Action::CloseWindow { id } => format!("[con_id={}] kill", id),
+            Action::NextLayout => String::from("input type:keyboard xkb_switch_layout next"),
+            Action::PrevLayout => String::from("input type:keyboard xkb_switch_layout prev"),
+        };
+        self.run_command(&command)
+    }
+
+    fn spawn_event_stream(&self, on_event: Box<dyn FnMut() -> bool + Send>) {
+        let sway = Self::with_socket(self.path.clone());
+        // Whatever the bar shows may be stale after a disconnect
+        compositor::spawn_reconnecting(
+            move |on_event| sway.follow_events(on_event),
+            on_event,
+            |on_event| on_event(),
+        );
+    }
+}
+
+// Leaves of the tree are windows; remember which workspace they sit under
+fn collect_windows(node: &Node, workspace: Option<u64>, windows: &mut Vec<Window>) {
+    let workspace = match node.kind.as_str() {
+        "workspace" if node.name.as_deref() == Some(SCRATCHPAD) => None,
+        "workspace" => Some(node.id),
+        _ => workspace,
+    };
+
+    let is_leaf = node.nodes.is_empty() && node.floating_nodes.is_empty();
+    if is_leaf && (node.kind == "con" || node.kind == "floating_con") {
+        let class = node.window_properties.as_ref().and_then(|p| p.class.clone());
+        windows.push(Window {
+            id: node.id,
+            title: node.name.clone(),
+            app_id: node.app_id.clone().or(class),
+            workspace_id: workspace,
+            is_focused: node.focused,
+        });
+    }
+
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_windows(child, workspace, windows);
+    }
+}
+
+// Quote an argument for a sway command
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_message(writer: &mut impl Write, kind: u32, payload: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    writer.write_all(&message)?;
+    writer.flush()
+}
+
+fn read_message(reader: &mut impl Read) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0; 14];
+    reader.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not an i3-ipc message"));
+    }
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    Ok((kind, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn messages_round_trip() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, GET_TREE, b"").unwrap();
+        write_message(&mut buffer, RUN_COMMAND, b"exit").unwrap();
+        assert_eq!(&buffer[..6], b"i3-ipc");
+        assert_eq!(buffer.len(), 14 + 14 + 4);
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(read_message(&mut reader).unwrap(), (GET_TREE, Vec::new()));
+        assert_eq!(read_message(&mut reader).unwrap(), (RUN_COMMAND, b"exit".to_vec()));
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        let mut reader = Cursor::new(b"HTTP/1.1 400 Bad Request".to_vec());
+        assert_eq!(read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn windows_are_the_leaves_of_the_tree() {
+        let tree: Node = serde_json::from_str(
+            r#"{"id": 1, "type": "root", "name": "root", "nodes": [
+                {"id": 2, "type": "output", "name": "__i3", "nodes": [
+                    {"id": 3, "type": "workspace", "name": "__i3_scratch", "floating_nodes": [
+                        {"id": 4, "type": "floating_con", "name": "notes", "app_id": "gedit"}
+                    ]}
+                ]},
+                {"id": 5, "type": "output", "name": "DP-1", "nodes": [
+                    {"id": 6, "type": "workspace", "name": "1", "nodes": [
+                        {"id": 7, "type": "con", "name": null, "nodes": [
+                            {"id": 8, "type": "con", "name": "vim", "app_id": "foot", "focused": true},
+                            {"id": 9, "type": "con", "name": "Steam", "app_id": null,
+                             "window_properties": {"class": "steam"}}
+                        ]}
+                    ], "floating_nodes": [
+                        {"id": 10, "type": "floating_con", "name": "calc", "app_id": "qalculate"}
+                    ]},
+                    {"id": 11, "type": "workspace", "name": "2", "nodes": []}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, &mut windows);
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|w| (w.id, w.app_id.as_deref().unwrap(), w.workspace_id, w.is_focused))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (4, "gedit", None, false),
+                (8, "foot", Some(6), true),
+                (9, "steam", Some(6), false),
+                (10, "qalculate", Some(6), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn command_arguments_are_quoted() {
+        assert_eq!(quote("1: web"), r#""1: web""#);
+        assert_eq!(quote(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+    }
+}
@@ -0,0 +1,47 @@
+// Test helpers shared by the module tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A throwaway directory tree standing in for /sys or /proc, removed on drop
+pub struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "oxidebar-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write `contents` to `relative`, creating parent directories
+    pub fn write(&self, relative: &str, contents: &str) -> &Self {
+        let path = self.root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    pub fn mkdir(&self, relative: &str) -> &Self {
+        fs::create_dir_all(self.root.join(relative)).unwrap();
+        self
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,185 @@
+// HyprlandIpc against fake Hyprland sockets
+
+mod support;
+
+use oxidebar::bar::Button;
+use oxidebar::compositor::{Action, Compositor};
+use oxidebar::hyprland::HyprlandIpc;
+use serde_json::json;
+use std::sync::mpsc;
+use std::time::Duration;
+use support::bar_with_hyprland;
+use support::fake_hyprland::*;
+
+fn two_monitors(hyprland: &FakeHyprland) {
+    hyprland.set_workspaces(json!([
+        workspace(3, "HDMI-A-1"),
+        workspace(1, "DP-1"),
+        workspace(2, "DP-1"),
+        workspace(-98, "DP-1"),
+    ]));
+    hyprland.set_monitors(json!([monitor("DP-1", 2, true), monitor("HDMI-A-1", 3, false)]));
+}
+
+#[test]
+fn workspaces_follow_the_monitors() {
+    let hyprland = FakeHyprland::start();
+    two_monitors(&hyprland);
+    let ipc = HyprlandIpc::with_dir(hyprland.dir());
+
+    // The special workspace stays off the bar
+    assert_eq!(ipc.workspace_summary().0, "1 [2] 3");
+    let states: Vec<_> = ipc
+        .workspaces()
+        .unwrap()
+        .into_iter()
+        .map(|w| (w.id, w.is_active, w.is_focused))
+        .collect();
+    assert_eq!(states, [(3, true, false), (1, false, false), (2, true, true)]);
+
+    drop(hyprland);
+    assert_eq!(ipc.workspace_summary().0, "WS ?");
+}
+
+#[test]
+fn windows_and_the_active_window() {
+    let hyprland = FakeHyprland::start();
+    hyprland.set_clients(json!([
+        client("0x1a", "vim", "foot", 1),
+        client("0x2b", "Mozilla Firefox", "firefox", 2),
+    ]));
+    let ipc = HyprlandIpc::with_dir(hyprland.dir());
+
+    // Nothing focused yet
+    assert_eq!(ipc.focused_window().unwrap(), None);
+    assert!(ipc.windows().unwrap().iter().all(|w| !w.is_focused));
+
+    let mut vim = client("0x1a", "vim", "foot", 1);
+    let mut firefox = client("0x2b", "Mozilla Firefox", "firefox", 2);
+    vim["focusHistoryID"] = json!(1);
+    firefox["focusHistoryID"] = json!(0);
+    hyprland.set_clients(json!([vim, firefox]));
+    hyprland.set_active_window(firefox);
+    let focused = ipc.focused_window().unwrap().unwrap();
+    assert_eq!((focused.id, focused.app_id.as_deref()), (0x2b, Some("firefox")));
+    let windows = ipc.windows().unwrap();
+    let ids: Vec<_> = windows.iter().map(|w| (w.id, w.workspace_id, w.is_focused)).collect();
+    assert_eq!(ids, [(0x1a, Some(1), false), (0x2b, Some(2), true)]);
+}
+
+#[test]
+fn actions_are_dispatched() {
+    let hyprland = FakeHyprland::start();
+    let ipc = HyprlandIpc::with_dir(hyprland.dir());
+
+    ipc.action(Action::FocusWorkspace { id: 4 }).unwrap();
+    ipc.action(Action::FocusWindow { id: 0x2b }).unwrap();
+    ipc.action(Action::CloseWindow { id: 0x2b }).unwrap();
+    ipc.action(Action::PrevLayout).unwrap();
+    assert_eq!(
+        hyprland.commands(),
+        [
+            "dispatch workspace 4",
+            "dispatch focuswindow address:0x2b",
+            "dispatch closewindow address:0x2b",
+            "switchxkblayout all prev",
+        ]
+    );
+
+    hyprland.reply("dispatch workspace 9", "Invalid workspace");
+    let error = ipc.action(Action::FocusWorkspace { id: 9 }).unwrap_err();
+    assert_eq!(error.to_string(), "Hyprland: Invalid workspace");
+}
+
+#[test]
+fn layouts_come_from_the_main_keyboard() {
+    let hyprland = FakeHyprland::start();
+    let devices = |main: serde_json::Value| {
+        json!({"mice": [], "keyboards": [
+            {"name": "power-button", "layout": "us", "active_keymap": "English (US)",
+             "active_layout_index": 0, "main": false},
+            main,
+        ]})
+    };
+    hyprland.reply(
+        "j/devices",
+        devices(json!({"name": "at-keyboard", "layout": "us, de", "active_keymap": "German",
+                       "active_layout_index": 1, "main": true})),
+    );
+    let ipc = HyprlandIpc::with_dir(hyprland.dir());
+
+    let layouts = ipc.keyboard_layouts().unwrap();
+    assert_eq!((layouts.names, layouts.current_idx), (vec!["us".to_string(), "de".to_string()], 1));
+
+    // Older releases only name the active layout
+    hyprland.reply(
+        "j/devices",
+        devices(json!({"name": "at-keyboard", "layout": "us,de", "active_keymap": "German", "main": true})),
+    );
+    let layouts = ipc.keyboard_layouts().unwrap();
+    assert_eq!((layouts.names, layouts.current_idx), (vec!["German".to_string()], 0));
+}
+
+#[test]
+fn clicking_a_workspace_switches_to_it() {
+    let hyprland = FakeHyprland::start();
+    two_monitors(&hyprland);
+    let mut bar = bar_with_hyprland(
+        &hyprland,
+        r#"
+        modules_left = ["workspaces"]
+        modules_right = []
+
+        [style]
+        padding = 10
+        "#,
+    );
+    bar.render(400, 30);
+
+    // "1 [2] 3"
+    assert!(bar.click(10.0 + 6.0 * 6.0, 15.0, Button::Left));
+    assert!(bar.click(10.0 + 6.0 * 3.0, 15.0, Button::Left));
+    assert!(!bar.click(10.0 + 6.0, 15.0, Button::Left));
+    // Hyprland can't deactivate workspaces, so middle-click isn't bound
+    assert!(!bar.click(10.0 + 6.0 * 6.0, 15.0, Button::Middle));
+    assert_eq!(hyprland.commands(), ["dispatch workspace 3", "dispatch workspace 2"]);
+}
+
+#[test]
+fn event_lines_trigger_redraws() {
+    let hyprland = FakeHyprland::start();
+    let ipc = HyprlandIpc::with_dir(hyprland.dir());
+
+    let (sender, events) = mpsc::channel();
+    ipc.spawn_event_stream(Box::new(move || sender.send(()).is_ok()));
+    hyprland.wait_for_subscribers(1);
+
+    hyprland.emit("workspace>>2");
+    hyprland.emit("activewindow>>firefox,Mozilla Firefox");
+    for _ in 0..2 {
+        assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+    }
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+
+    // Losing the socket prompts a redraw, then a reconnect
+    hyprland.drop_subscribers();
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+    hyprland.wait_for_subscribers(1);
+    hyprland.emit("activelayout>>at-keyboard,German");
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+}
+
+#[test]
+fn unresponsive_hyprland_times_out() {
+    let dir = std::env::temp_dir().join(format!("oxidebar-hypr-{}-silent", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let _ = std::fs::remove_file(dir.join(".socket.sock"));
+    // Accepts connections but never answers
+    let _listener = std::os::unix::net::UnixListener::bind(dir.join(".socket.sock")).unwrap();
+    let ipc = HyprlandIpc::with_dir(&dir);
+
+    let started = std::time::Instant::now();
+    assert!(ipc.workspaces().is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,92 @@
+// The keyboard layout module against a fake niri
+
+mod support;
+
+use oxidebar::bar::{BarRenderer, Button};
+use oxidebar::compositor::{Compositor, KeyboardLayouts};
+use oxidebar::niri::{NiriIpc, SocketTransport};
+use serde_json::json;
+use std::sync::mpsc;
+use std::time::Duration;
+use support::bar_with_niri;
+use support::fake_niri::FakeNiri;
+
+fn bar(niri: &FakeNiri) -> BarRenderer {
+    bar_with_niri(
+        niri,
+        r#"
+        modules_left = ["keyboard_layout"]
+        modules_right = []
+
+        [style]
+        padding = 10
+
+        [module_config.keyboard_layout.labels]
+        "English (US)" = "us"
+        "#,
+    )
+}
+
+#[test]
+fn clicks_cycle_layouts() {
+    let niri = FakeNiri::start();
+    niri.set_keyboard_layouts(&["English (US)", "German"], 0);
+
+    let mut bar = bar(&niri);
+    bar.render(400, 30);
+
+    // "us", two cells wide
+    assert!(bar.click(12.0, 15.0, Button::Left));
+    assert!(bar.click(20.0, 15.0, Button::Right));
+    assert!(!bar.click(20.0, 15.0, Button::Middle));
+    assert!(!bar.click(30.0, 15.0, Button::Left));
+
+    let switches: Vec<String> = niri
+        .requests_after_handshake()
+        .into_iter()
+        .filter(|r| r.contains("Action"))
+        .collect();
+    assert_eq!(
+        switches,
+        [
+            r#"{"Action":{"SwitchLayout":{"layout":"Next"}}}"#,
+            r#"{"Action":{"SwitchLayout":{"layout":"Prev"}}}"#,
+        ]
+    );
+}
+
+#[test]
+fn unavailable_layouts_hide_the_module() {
+    let niri = FakeNiri::start();
+    niri.reply("KeyboardLayouts", json!({"Err": "no keyboard"}));
+
+    let mut bar = bar(&niri);
+    bar.render(400, 30);
+
+    assert!(!bar.click(12.0, 15.0, Button::Left));
+}
+
+#[test]
+fn layouts_follow_the_event_stream() {
+    let niri = FakeNiri::start();
+    niri.set_initial_events(vec![json!({"KeyboardLayoutsChanged": {
+        "keyboard_layouts": {"names": ["English (US)", "German"], "current_idx": 0}
+    }})]);
+    let ipc = NiriIpc::with_transport(SocketTransport::new(niri.path()));
+
+    let (sender, events) = mpsc::channel();
+    Compositor::spawn_event_stream(&ipc, Box::new(move || sender.send(()).is_ok()));
+    events.recv_timeout(Duration::from_secs(1)).unwrap();
+    let layouts = |current_idx| KeyboardLayouts {
+        names: vec!["English (US)".into(), "German".into()],
+        current_idx,
+    };
+    assert_eq!(Compositor::keyboard_layouts(&ipc).unwrap(), layouts(0));
+
+    niri.emit(json!({"KeyboardLayoutSwitched": {"idx": 1}}));
+    events.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(Compositor::keyboard_layouts(&ipc).unwrap(), layouts(1));
+
+    // Everything came from the events rather than a request per redraw
+    assert_eq!(niri.requests_after_handshake(), ["\"EventStream\""]);
+}
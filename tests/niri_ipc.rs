@@ -0,0 +1,173 @@
+// NiriIpc against a fake niri socket
+
+mod support;
+
+use oxidebar::niri::{Action, Error, Event, LayoutSwitchTarget, NiriIpc, SocketTransport, Transport};
+use serde_json::json;
+use std::sync::mpsc;
+use std::time::Duration;
+use support::fake_niri::{window, workspace, FakeNiri};
+
+fn connect(niri: &FakeNiri) -> NiriIpc {
+    NiriIpc::with_transport(SocketTransport::new(niri.path()))
+}
+
+#[test]
+fn workspaces_are_summarized_in_index_order() {
+    let niri = FakeNiri::start();
+    niri.set_workspaces(json!([
+        workspace(7, 2, "DP-1", true),
+        workspace(5, 1, "DP-1", false),
+        workspace(9, 3, "DP-1", false),
+    ]));
+    let ipc = connect(&niri);
+
+    assert_eq!(ipc.get_workspace_summary(), "1 [2] 3");
+    let focused = ipc.workspaces().unwrap().into_iter().find(|w| w.is_focused);
+    assert_eq!(focused.map(|w| w.id), Some(7));
+
+    // The version is only checked once per client
+    assert_eq!(niri.requests(), ["\"Version\"", "\"Workspaces\"", "\"Workspaces\""]);
+}
+
+#[test]
+fn windows_and_focus_are_typed() {
+    let niri = FakeNiri::start();
+    niri.set_windows(json!([
+        window(1, "README.md - Helix", "Helix", 5, false),
+        window(2, "Mozilla Firefox", "firefox", 5, true),
+    ]));
+    niri.set_focused_window(window(2, "Mozilla Firefox", "firefox", 5, true));
+    let ipc = connect(&niri);
+
+    let windows = ipc.windows().unwrap();
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0].app_id.as_deref(), Some("Helix"));
+
+    let focused = ipc.focused_window().unwrap().unwrap();
+    assert_eq!((focused.id, focused.title.as_deref()), (2, Some("Mozilla Firefox")));
+
+    niri.set_focused_window(json!(null));
+    assert_eq!(ipc.focused_window().unwrap(), None);
+}
+
+#[test]
+fn keyboard_layouts_and_actions() {
+    let niri = FakeNiri::start();
+    niri.set_keyboard_layouts(&["English (US)", "German"], 1);
+    let ipc = connect(&niri);
+
+    let layouts = ipc.keyboard_layouts().unwrap();
+    assert_eq!(layouts.names[layouts.current_idx as usize], "German");
+
+    ipc.action(Action::SwitchLayout { layout: LayoutSwitchTarget::Next }).unwrap();
+    ipc.action(Action::FocusWindow { id: 4 }).unwrap();
+    assert_eq!(
+        niri.requests_after_handshake(),
+        [
+            r#""KeyboardLayouts""#,
+            r#"{"Action":{"SwitchLayout":{"layout":"Next"}}}"#,
+            r#"{"Action":{"FocusWindow":{"id":4}}}"#,
+        ]
+    );
+}
+
+#[test]
+fn refused_requests_carry_the_niri_message() {
+    let niri = FakeNiri::start();
+    niri.reply("Action", json!({"Err": "window not found"}));
+    let ipc = connect(&niri);
+
+    match ipc.action(Action::CloseWindow { id: Some(99) }) {
+        Err(Error::Niri(message)) => assert_eq!(message, "window not found"),
+        other => panic!("expected niri to refuse, got {:?}", other),
+    }
+}
+
+#[test]
+fn old_niri_gets_a_clear_message() {
+    let niri = FakeNiri::start();
+    niri.respond_ok("Version", json!("0.1.7"));
+    niri.set_workspaces(json!([]));
+    let ipc = connect(&niri);
+
+    let error = ipc.workspaces().unwrap_err();
+    assert_eq!(error.to_string(), "niri 0.1.7 is too old, oxidebar needs 0.1.9 or newer");
+    assert_eq!(ipc.get_workspace_summary(), "niri 0.1.7 unsupported");
+}
+
+#[test]
+fn no_workspaces_reads_as_empty() {
+    let niri = FakeNiri::start();
+    niri.set_workspaces(json!([]));
+
+    assert_eq!(connect(&niri).get_workspace_summary(), "Empty");
+}
+
+#[test]
+fn error_replies_and_missing_sockets_show_unknown() {
+    let niri = FakeNiri::start();
+    niri.reply("Workspaces", json!({"Err": "compositor is shutting down"}));
+    let ipc = connect(&niri);
+    assert_eq!(ipc.get_workspace_summary(), "WS ?");
+
+    drop(niri);
+    assert_eq!(ipc.get_workspace_summary(), "WS ?");
+}
+
+#[test]
+fn event_stream_delivers_emitted_events() {
+    let niri = FakeNiri::start();
+    niri.set_initial_events(vec![json!({"WorkspacesChanged": {"workspaces": []}})]);
+    let ipc = connect(&niri);
+
+    let (sender, events) = mpsc::channel();
+    ipc.spawn_event_stream(move |event| sender.send(event).is_ok());
+    niri.wait_for_subscribers(1);
+
+    // The initial event arrives without any prompting
+    let next = || events.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(next(), Some(Event::WorkspacesChanged { workspaces: Vec::new() }));
+
+    niri.emit(json!({"WorkspaceActivated": {"id": 5, "focused": true}}));
+    niri.emit(json!({"WindowFocusChanged": {"id": null}}));
+    assert_eq!(next(), Some(Event::WorkspaceActivated { id: 5, focused: true }));
+    assert_eq!(next(), Some(Event::WindowFocusChanged { id: None }));
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+    assert_eq!(niri.requests_after_handshake(), ["\"EventStream\""]);
+}
+
+#[test]
+fn event_stream_reconnects_after_niri_restarts() {
+    let niri = FakeNiri::start();
+    let ipc = connect(&niri);
+
+    let (sender, events) = mpsc::channel();
+    ipc.spawn_event_stream(move |_| sender.send(()).is_ok());
+    niri.wait_for_subscribers(1);
+
+    // Losing the stream prompts a redraw, since the bar may now be stale
+    niri.drop_subscribers();
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+
+    niri.wait_for_subscribers(1);
+    niri.emit(json!({"WorkspaceActivated": {"id": 1, "focused": true}}));
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+    assert_eq!(niri.requests_after_handshake(), ["\"EventStream\"", "\"EventStream\""]);
+}
+
+#[test]
+fn replies_follow_the_script() {
+    let niri = FakeNiri::start();
+    niri.set_keyboard_layouts(&["English (US)", "German"], 1);
+    let transport = SocketTransport::new(niri.path());
+
+    let reply = transport.request("\"KeyboardLayouts\"").unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&reply).unwrap(),
+        json!({"Ok": {"KeyboardLayouts": {"names": ["English (US)", "German"], "current_idx": 1}}})
+    );
+
+    let reply = transport.request("\"Outputs\"").unwrap();
+    assert!(reply.contains("\"Err\""), "{}", reply);
+}
@@ -0,0 +1,172 @@
+// Golden-image tests for the renderer
+//
+// Each case renders a bar headless with pinned module content and compares
+// its pixels against tests/snapshots/<name>.png. Run with UPDATE_SNAPSHOTS=1 to
+// write new goldens after an intended change; on a mismatch the actual image
+// is saved next to the golden as <name>.actual.png.
+
+use oxidebar::config::Config;
+use oxidebar::headless;
+use oxidebar::modules::{ModuleOutput, ModuleState};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Module lists default to empty when a config file leaves them out
+const DEFAULT_MODULES: &str = r##"
+    modules_left = ["workspaces"]
+    modules_right = ["network", "battery", "clock"]
+"##;
+
+fn modules(outputs: &[(&str, &str, Option<ModuleState>)]) -> HashMap<String, ModuleOutput> {
+    outputs
+        .iter()
+        .map(|(name, text, state)| {
+            let output = ModuleOutput { text: text.to_string(), state: *state };
+            (name.to_string(), output)
+        })
+        .collect()
+}
+
+fn default_modules() -> HashMap<String, ModuleOutput> {
+    modules(&[
+        ("workspaces", "1 2 [3] 4", None),
+        ("network", "WiFi home", None),
+        ("battery", "BAT 72%", None),
+        ("clock", "12:34:56", None),
+    ])
+}
+
+// Size and straight-alpha RGBA pixels of a golden image
+fn decode(path: &Path) -> (u32, u32, Vec<u8>) {
+    let file = std::fs::File::open(path).unwrap_or_else(|_| {
+        panic!("missing snapshot {}; run with UPDATE_SNAPSHOTS=1", path.display())
+    });
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().expect("snapshot should be a PNG");
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).expect("snapshot should decode");
+    assert_eq!(
+        (info.color_type, info.bit_depth),
+        (png::ColorType::Rgba, png::BitDepth::Eight),
+        "{} should be 8-bit RGBA",
+        path.display()
+    );
+    pixels.truncate(info.buffer_size());
+    (info.width, info.height, pixels)
+}
+
+fn assert_snapshot(name: &str, config: &str, width: u32, overrides: HashMap<String, ModuleOutput>) {
+    let config = Config::from_toml(config).expect("test config should parse");
+    let height = config.height;
+    let image = headless::render(config, width, height, overrides);
+
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots");
+    let golden = dir.join(format!("{}.png", name));
+    let actual = dir.join(format!("{}.actual.png", name));
+    let png = || image.to_png().expect("rendered bar should encode");
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&golden, png()).unwrap();
+        let _ = std::fs::remove_file(&actual);
+        return;
+    }
+
+    // Compare pixels rather than bytes so the encoder's choices don't matter
+    let expected = decode(&golden);
+    if expected != (image.width, image.height, image.to_rgba()) {
+        std::fs::write(&actual, png()).unwrap();
+        panic!(
+            "{} differs from its snapshot; see {}",
+            name,
+            actual.display()
+        );
+    }
+    let _ = std::fs::remove_file(&actual);
+}
+
+#[test]
+fn default_bar() {
+    assert_snapshot("default_bar", DEFAULT_MODULES, 480, default_modules());
+}
+
+#[test]
+fn battery_states() {
+    let config = r##"
+        modules_left = []
+        modules_right = ["battery", "clock"]
+    "##;
+    let mut outputs = modules(&[("battery", "BAT 12%", None), ("clock", "09:00", None)]);
+    assert_snapshot("battery_critical", config, 240, outputs.clone());
+
+    outputs.get_mut("clock").unwrap().state = Some(ModuleState::Warning);
+    outputs.get_mut("battery").unwrap().text = "BAT 90%".into();
+    assert_snapshot("clock_warning", config, 240, outputs);
+}
+
+#[test]
+fn rounded_pills() {
+    let config = r##"
+        [style]
+        module_background = "#313244"
+        module_border = "#89b4fa"
+        module_border_width = 1
+        module_radius = 6
+        module_padding = 6
+    "##;
+    let config = format!("{}{}", DEFAULT_MODULES, config);
+    assert_snapshot("rounded_pills", &config, 480, default_modules());
+}
+
+#[test]
+fn arrow_separators() {
+    let config = r##"
+        [style]
+        module_background = "#313244"
+        module_padding = 6
+
+        [style.separator]
+        kind = "arrow"
+
+        [module_style.battery]
+        background = "#45475a"
+
+        [module_style.clock]
+        background = "#89b4fa"
+        foreground = "#1e1e2e"
+    "##;
+    let config = format!("{}{}", DEFAULT_MODULES, config);
+    assert_snapshot("arrows", &config, 480, default_modules());
+}
+
+#[test]
+fn translucent_gradient_background() {
+    let config = r##"
+        [style]
+        background = "linear-gradient(90deg, #1e1e2e, #45475a)"
+        opacity = 0.8
+
+        [style.separator]
+        kind = "round"
+    "##;
+    let config = format!("{}{}", DEFAULT_MODULES, config);
+    assert_snapshot("gradient_translucent", &config, 480, default_modules());
+}
+
+#[test]
+fn long_text_is_ellipsized() {
+    let config = r##"
+        modules_left = ["workspaces"]
+        modules_center = ["network"]
+        modules_right = ["clock"]
+
+        [module_layout.network]
+        max_width = 60
+    "##;
+    let outputs = modules(&[
+        ("workspaces", "1 2 3 4 5 6 7 8 9", None),
+        ("network", "WiFi a-very-long-network-name", None),
+        ("clock", "12:34:56", None),
+    ]);
+    assert_snapshot("ellipsized", config, 240, outputs);
+}
@@ -0,0 +1,212 @@
+// A stand-in for Hyprland's IPC sockets
+//
+// Serves `.socket.sock` and `.socket2.sock` from a temporary instance
+// directory. Each request connection gets one scripted reply and is closed;
+// event connections stay open and receive whatever the test emits.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct State {
+    // Request -> reply, e.g. "j/workspaces" -> "[...]"
+    replies: HashMap<String, String>,
+    // Every request received, in order
+    requests: Vec<String>,
+    subscribers: Vec<UnixStream>,
+}
+
+pub struct FakeHyprland {
+    dir: PathBuf,
+    state: Arc<(Mutex<State>, Condvar)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl FakeHyprland {
+    pub fn start() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "oxidebar-hypr-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create fake Hyprland dir");
+        let requests = UnixListener::bind(dir.join(".socket.sock")).expect("bind .socket.sock");
+        let events = UnixListener::bind(dir.join(".socket2.sock")).expect("bind .socket2.sock");
+
+        let server = Self {
+            dir,
+            state: Arc::default(),
+            stop: Arc::default(),
+        };
+        server.reply("j/activewindow", json!({}));
+
+        let (state, stop) = (server.state.clone(), server.stop.clone());
+        thread::spawn(move || {
+            for stream in requests.incoming() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(stream) = stream {
+                    let state = state.clone();
+                    thread::spawn(move || serve(stream, &state));
+                }
+            }
+        });
+
+        let (state, stop) = (server.state.clone(), server.stop.clone());
+        thread::spawn(move || {
+            for stream in events.incoming() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(stream) = stream {
+                    let (state, changed) = &*state;
+                    state.lock().unwrap().subscribers.push(stream);
+                    changed.notify_all();
+                }
+            }
+        });
+        server
+    }
+
+    /// Instance directory to hand to the client
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Answer `request` with `reply` from now on
+    pub fn reply(&self, request: &str, reply: impl ToString) {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().replies.insert(request.to_string(), reply.to_string());
+    }
+
+    pub fn set_workspaces(&self, workspaces: Value) {
+        self.reply("j/workspaces", workspaces);
+    }
+
+    pub fn set_monitors(&self, monitors: Value) {
+        self.reply("j/monitors", monitors);
+    }
+
+    pub fn set_clients(&self, clients: Value) {
+        self.reply("j/clients", clients);
+    }
+
+    pub fn set_active_window(&self, client: Value) {
+        self.reply("j/activewindow", client);
+    }
+
+    /// Send an `EVENT>>DATA` line to every event connection
+    pub fn emit(&self, event: &str) {
+        let (state, _) = &*self.state;
+        let line = format!("{}\n", event);
+        state
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+    }
+
+    /// Close every event connection, as if Hyprland restarted
+    pub fn drop_subscribers(&self) {
+        let (state, _) = &*self.state;
+        for stream in state.lock().unwrap().subscribers.drain(..) {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Block until `count` event connections are open
+    pub fn wait_for_subscribers(&self, count: usize) {
+        let (state, changed) = &*self.state;
+        let guard = state.lock().unwrap();
+        let (_guard, timeout) = changed
+            .wait_timeout_while(guard, Duration::from_secs(5), |s| s.subscribers.len() < count)
+            .unwrap();
+        assert!(!timeout.timed_out(), "no client connected to the event socket");
+    }
+
+    /// Every request received so far
+    pub fn requests(&self) -> Vec<String> {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().requests.clone()
+    }
+
+    /// Requests received so far that weren't `j/` queries
+    pub fn commands(&self) -> Vec<String> {
+        let mut requests = self.requests();
+        requests.retain(|r| !r.starts_with("j/"));
+        requests
+    }
+}
+
+impl Drop for FakeHyprland {
+    fn drop(&mut self) {
+        // Wake the accept loops so they notice they should stop
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = UnixStream::connect(self.dir.join(".socket.sock"));
+        let _ = UnixStream::connect(self.dir.join(".socket2.sock"));
+        self.drop_subscribers();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn serve(mut stream: UnixStream, state: &(Mutex<State>, Condvar)) {
+    // Like Hyprland, take whatever arrives in one read as the request
+    let mut buffer = [0; 8192];
+    let Ok(length) = stream.read(&mut buffer) else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buffer[..length]).into_owned();
+
+    let (lock, _) = state;
+    let reply = {
+        let mut state = lock.lock().unwrap();
+        state.requests.push(request.clone());
+        match state.replies.get(&request) {
+            Some(reply) => reply.clone(),
+            None if request.starts_with("dispatch ") || request.starts_with("switchxkblayout ") => {
+                String::from("ok")
+            }
+            None => String::from("unknown request"),
+        }
+    };
+    let _ = stream.write_all(reply.as_bytes());
+}
+
+/// A workspace as `j/workspaces` reports it
+pub fn workspace(id: i64, monitor: &str) -> Value {
+    json!({"id": id, "name": id.to_string(), "monitor": monitor, "windows": 1})
+}
+
+/// A monitor as `j/monitors` reports it
+pub fn monitor(name: &str, active_workspace: i64, focused: bool) -> Value {
+    json!({
+        "id": 0,
+        "name": name,
+        "activeWorkspace": {"id": active_workspace, "name": active_workspace.to_string()},
+        "focused": focused,
+    })
+}
+
+/// A window as `j/clients` and `j/activewindow` report it
+pub fn client(address: &str, title: &str, class: &str, workspace: i64) -> Value {
+    json!({
+        "address": address,
+        "mapped": true,
+        "hidden": false,
+        "workspace": {"id": workspace, "name": workspace.to_string()},
+        "class": class,
+        "title": title,
+    })
+}
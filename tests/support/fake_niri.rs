@@ -0,0 +1,229 @@
+// A stand-in for niri's IPC socket
+//
+// Serves the same JSON-lines protocol on a temporary Unix socket: each
+// connection sends one request line and gets one reply line back. Replies are
+// scripted per request; `EventStream` connections stay open and receive
+// whatever the test emits.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct State {
+    // Request name -> reply line
+    replies: HashMap<String, String>,
+    // Every request line received, in order
+    requests: Vec<String>,
+    // Open event streams
+    subscribers: Vec<UnixStream>,
+    // Events sent to each new stream right after its reply
+    initial_events: Vec<String>,
+}
+
+pub struct FakeNiri {
+    path: PathBuf,
+    state: Arc<(Mutex<State>, Condvar)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl FakeNiri {
+    pub fn start() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "oxidebar-niri-{}-{}.sock",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("bind fake niri socket");
+
+        let server = Self {
+            path,
+            state: Arc::default(),
+            stop: Arc::default(),
+        };
+        server.respond_ok("Version", json!("25.02 (fake)"));
+        server.reply("Action", json!({"Ok": "Handled"}));
+        server.reply("EventStream", json!({"Ok": "Handled"}));
+
+        let state = server.state.clone();
+        let stop = server.stop.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(stream) = stream {
+                    let state = state.clone();
+                    thread::spawn(move || serve(stream, &state));
+                }
+            }
+        });
+        server
+    }
+
+    /// Socket path to hand to the client, as NIRI_SOCKET would
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Answer `request` with `reply` from now on. `request` is the request
+    /// name, e.g. "Workspaces", or "Action" for any action.
+    pub fn reply(&self, request: &str, reply: Value) {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().replies.insert(request.to_string(), reply.to_string());
+    }
+
+    /// Answer `request` with a successful response carrying `payload`
+    pub fn respond_ok(&self, request: &str, payload: Value) {
+        self.reply(request, json!({"Ok": {request: payload}}));
+    }
+
+    pub fn set_workspaces(&self, workspaces: Value) {
+        self.respond_ok("Workspaces", workspaces);
+    }
+
+    pub fn set_windows(&self, windows: Value) {
+        self.respond_ok("Windows", windows);
+    }
+
+    pub fn set_focused_window(&self, window: Value) {
+        self.respond_ok("FocusedWindow", window);
+    }
+
+    pub fn set_keyboard_layouts(&self, names: &[&str], current_idx: u8) {
+        self.respond_ok("KeyboardLayouts", json!({"names": names, "current_idx": current_idx}));
+    }
+
+    /// Events every new event stream receives after the reply
+    pub fn set_initial_events(&self, events: Vec<Value>) {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().initial_events = events.iter().map(Value::to_string).collect();
+    }
+
+    /// Send an event to every open event stream
+    pub fn emit(&self, event: Value) {
+        let (state, _) = &*self.state;
+        let line = format!("{}\n", event);
+        state
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+    }
+
+    /// Close every open event stream, as if niri restarted
+    pub fn drop_subscribers(&self) {
+        let (state, _) = &*self.state;
+        for stream in state.lock().unwrap().subscribers.drain(..) {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Block until `count` event streams are open
+    pub fn wait_for_subscribers(&self, count: usize) {
+        let (state, changed) = &*self.state;
+        let guard = state.lock().unwrap();
+        let (_guard, timeout) = changed
+            .wait_timeout_while(guard, Duration::from_secs(5), |s| s.subscribers.len() < count)
+            .unwrap();
+        assert!(!timeout.timed_out(), "no client subscribed to events");
+    }
+
+    /// Every request line received so far
+    pub fn requests(&self) -> Vec<String> {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().requests.clone()
+    }
+
+    /// Requests received so far, without the client's version check
+    pub fn requests_after_handshake(&self) -> Vec<String> {
+        let mut requests = self.requests();
+        requests.retain(|r| r != "\"Version\"");
+        requests
+    }
+}
+
+impl Drop for FakeNiri {
+    fn drop(&mut self) {
+        // Wake the accept loop so it notices it should stop
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = UnixStream::connect(&self.path);
+        self.drop_subscribers();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, state: &(Mutex<State>, Condvar)) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut line = String::new();
+    if BufReader::new(stream).read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let line = line.trim_end().to_string();
+
+    // Requests are either a bare string or an object keyed by the request
+    let name = match serde_json::from_str::<Value>(&line) {
+        Ok(Value::String(name)) => name,
+        Ok(Value::Object(map)) => map.keys().next().cloned().unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    let (lock, changed) = state;
+    let mut state = lock.lock().unwrap();
+    state.requests.push(line);
+    let reply = state
+        .replies
+        .get(&name)
+        .cloned()
+        .unwrap_or_else(|| json!({"Err": format!("unhandled request: {}", name)}).to_string());
+    if writeln!(writer, "{}", reply).is_err() {
+        return;
+    }
+
+    if name == "EventStream" {
+        for event in &state.initial_events {
+            let _ = writeln!(writer, "{}", event);
+        }
+        state.subscribers.push(writer);
+        changed.notify_all();
+    }
+}
+
+/// A workspace as niri reports it
+pub fn workspace(id: u64, idx: u8, output: &str, is_focused: bool) -> Value {
+    json!({
+        "id": id,
+        "idx": idx,
+        "name": null,
+        "output": output,
+        "is_urgent": false,
+        "is_active": is_focused,
+        "is_focused": is_focused,
+        "active_window_id": null,
+    })
+}
+
+/// A window as niri reports it
+pub fn window(id: u64, title: &str, app_id: &str, workspace_id: u64, is_focused: bool) -> Value {
+    json!({
+        "id": id,
+        "title": title,
+        "app_id": app_id,
+        "pid": null,
+        "workspace_id": workspace_id,
+        "is_focused": is_focused,
+        "is_floating": false,
+    })
+}
@@ -0,0 +1,221 @@
+// A stand-in for sway's IPC socket
+//
+// Speaks i3-ipc framing on a temporary Unix socket. Replies are scripted per
+// message type; SUBSCRIBE connections stay open and receive whatever the test
+// emits.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const RUN_COMMAND: u32 = 0;
+pub const GET_WORKSPACES: u32 = 1;
+pub const SUBSCRIBE: u32 = 2;
+pub const GET_TREE: u32 = 4;
+pub const GET_INPUTS: u32 = 100;
+
+pub const WORKSPACE_EVENT: u32 = 0x8000_0000;
+pub const WINDOW_EVENT: u32 = 0x8000_0003;
+
+#[derive(Default)]
+struct State {
+    // Message type -> reply payload
+    replies: HashMap<u32, String>,
+    // Every message received, as (type, payload)
+    messages: Vec<(u32, String)>,
+    subscribers: Vec<UnixStream>,
+}
+
+pub struct FakeSway {
+    path: PathBuf,
+    state: Arc<(Mutex<State>, Condvar)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl FakeSway {
+    pub fn start() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "oxidebar-sway-{}-{}.sock",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("bind fake sway socket");
+
+        let server = Self {
+            path,
+            state: Arc::default(),
+            stop: Arc::default(),
+        };
+        server.reply(RUN_COMMAND, json!([{"success": true}]));
+        server.reply(SUBSCRIBE, json!({"success": true}));
+
+        let state = server.state.clone();
+        let stop = server.stop.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(stream) = stream {
+                    let state = state.clone();
+                    thread::spawn(move || serve(stream, &state));
+                }
+            }
+        });
+        server
+    }
+
+    /// Socket path to hand to the client, as SWAYSOCK would
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Answer messages of type `kind` with `reply` from now on
+    pub fn reply(&self, kind: u32, reply: Value) {
+        let (state, _) = &*self.state;
+        state.lock().unwrap().replies.insert(kind, reply.to_string());
+    }
+
+    pub fn set_workspaces(&self, workspaces: Value) {
+        self.reply(GET_WORKSPACES, workspaces);
+    }
+
+    pub fn set_tree(&self, tree: Value) {
+        self.reply(GET_TREE, tree);
+    }
+
+    /// Send an event to every subscriber
+    pub fn emit(&self, kind: u32, event: Value) {
+        let (state, _) = &*self.state;
+        let message = frame(kind, event.to_string().as_bytes());
+        state
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain_mut(|stream| stream.write_all(&message).is_ok());
+    }
+
+    /// Close every subscription, as if sway restarted
+    pub fn drop_subscribers(&self) {
+        let (state, _) = &*self.state;
+        for stream in state.lock().unwrap().subscribers.drain(..) {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Block until `count` clients are subscribed
+    pub fn wait_for_subscribers(&self, count: usize) {
+        let (state, changed) = &*self.state;
+        let guard = state.lock().unwrap();
+        let (_guard, timeout) = changed
+            .wait_timeout_while(guard, Duration::from_secs(5), |s| s.subscribers.len() < count)
+            .unwrap();
+        assert!(!timeout.timed_out(), "no client subscribed to events");
+    }
+
+    /// Payloads of every message of type `kind` received so far
+    pub fn messages(&self, kind: u32) -> Vec<String> {
+        let (state, _) = &*self.state;
+        let state = state.lock().unwrap();
+        state
+            .messages
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+}
+
+impl Drop for FakeSway {
+    fn drop(&mut self) {
+        // Wake the accept loop so it notices it should stop
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = UnixStream::connect(&self.path);
+        self.drop_subscribers();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn frame(kind: u32, payload: &[u8]) -> Vec<u8> {
+    let mut message = b"i3-ipc".to_vec();
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+fn serve(mut stream: UnixStream, state: &(Mutex<State>, Condvar)) {
+    // A connection may carry any number of messages
+    loop {
+        let mut header = [0; 14];
+        if stream.read_exact(&mut header).is_err() || &header[..6] != b"i3-ipc" {
+            return;
+        }
+        let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+        let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut payload = vec![0; length as usize];
+        if stream.read_exact(&mut payload).is_err() {
+            return;
+        }
+
+        let (lock, changed) = state;
+        let mut state = lock.lock().unwrap();
+        state.messages.push((kind, String::from_utf8_lossy(&payload).into_owned()));
+        let reply = state
+            .replies
+            .get(&kind)
+            .cloned()
+            .unwrap_or_else(|| json!({"success": false, "error": "unhandled message"}).to_string());
+        if stream.write_all(&frame(kind, reply.as_bytes())).is_err() {
+            return;
+        }
+
+        if kind == SUBSCRIBE {
+            if let Ok(subscriber) = stream.try_clone() {
+                state.subscribers.push(subscriber);
+                changed.notify_all();
+            }
+        }
+    }
+}
+
+/// A workspace as GET_WORKSPACES reports it
+pub fn workspace(id: u64, num: i32, name: &str, focused: bool) -> Value {
+    json!({
+        "id": id,
+        "num": num,
+        "name": name,
+        "output": "DP-1",
+        "visible": focused,
+        "focused": focused,
+        "urgent": false,
+    })
+}
+
+/// A window leaf for GET_TREE
+pub fn window(id: u64, title: &str, app_id: &str, focused: bool) -> Value {
+    json!({"id": id, "type": "con", "name": title, "app_id": app_id, "focused": focused, "nodes": []})
+}
+
+/// A tree of one output whose workspaces are (id, name, windows)
+pub fn tree(workspaces: Vec<(u64, &str, Vec<Value>)>) -> Value {
+    let workspaces: Vec<Value> = workspaces
+        .into_iter()
+        .map(|(id, name, windows)| {
+            json!({"id": id, "type": "workspace", "name": name, "nodes": windows})
+        })
+        .collect();
+    json!({"id": 1, "type": "root", "name": "root", "nodes": [
+        {"id": 2, "type": "output", "name": "DP-1", "nodes": workspaces}
+    ]})
+}
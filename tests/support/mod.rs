@@ -0,0 +1,57 @@
+// Shared helpers for integration tests
+
+pub mod fake_hyprland;
+pub mod fake_niri;
+pub mod fake_sway;
+
+use fake_hyprland::FakeHyprland;
+use fake_niri::FakeNiri;
+use fake_sway::FakeSway;
+use oxidebar::bar::BarRenderer;
+use oxidebar::compositor::Compositor;
+use oxidebar::config::Config;
+use oxidebar::hyprland::HyprlandIpc;
+use oxidebar::niri::{NiriIpc, SocketTransport};
+use oxidebar::sources::{Clock, Getifaddrs, ManualClock, Sources, Statvfs, SystemClock};
+use oxidebar::sway::SwayIpc;
+
+/// A renderer for `toml` that talks to `niri`
+#[allow(dead_code)]
+pub fn bar_with_niri(niri: &FakeNiri, toml: &str) -> BarRenderer {
+    bar_with_niri_at(niri, toml, Box::new(SystemClock))
+}
+
+/// A renderer for `toml` that talks to `niri` and tells time by `clock`
+#[allow(dead_code)]
+pub fn bar_with_niri_clock(niri: &FakeNiri, toml: &str, clock: &ManualClock) -> BarRenderer {
+    bar_with_niri_at(niri, toml, Box::new(clock.clone()))
+}
+
+fn bar_with_niri_at(niri: &FakeNiri, toml: &str, clock: Box<dyn Clock>) -> BarRenderer {
+    let niri = NiriIpc::with_transport(SocketTransport::new(niri.path()));
+    bar_with(Box::new(niri), toml, clock)
+}
+
+/// A renderer for `toml` that talks to `sway`
+#[allow(dead_code)]
+pub fn bar_with_sway(sway: &FakeSway, toml: &str) -> BarRenderer {
+    bar_with(Box::new(SwayIpc::with_socket(sway.path())), toml, Box::new(SystemClock))
+}
+
+/// A renderer for `toml` that talks to `hyprland`
+#[allow(dead_code)]
+pub fn bar_with_hyprland(hyprland: &FakeHyprland, toml: &str) -> BarRenderer {
+    bar_with(Box::new(HyprlandIpc::with_dir(hyprland.dir())), toml, Box::new(SystemClock))
+}
+
+fn bar_with(compositor: Box<dyn Compositor>, toml: &str, clock: Box<dyn Clock>) -> BarRenderer {
+    let sources = Sources {
+        sysfs: std::env::temp_dir(),
+        procfs: std::env::temp_dir(),
+        clock,
+        filesystems: Box::new(Statvfs),
+        interfaces: Box::new(Getifaddrs),
+        compositor: Some(compositor),
+    };
+    BarRenderer::with_sources(Config::from_toml(toml).unwrap(), sources)
+}
@@ -0,0 +1,160 @@
+// SwayIpc against a fake sway socket
+
+mod support;
+
+use oxidebar::bar::Button;
+use oxidebar::compositor::{Action, Compositor};
+use oxidebar::sway::SwayIpc;
+use serde_json::json;
+use std::sync::mpsc;
+use std::time::Duration;
+use support::bar_with_sway;
+use support::fake_sway::*;
+
+#[test]
+fn workspaces_are_summarized_by_number() {
+    let sway = FakeSway::start();
+    sway.set_workspaces(json!([
+        workspace(12, -1, "mail", false),
+        workspace(10, 1, "1", false),
+        workspace(11, 2, "2: web", true),
+        workspace(13, 4, "4", false),
+    ]));
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    // Named workspaces have no number and come after the numbered ones
+    assert_eq!(ipc.workspace_summary().0, "1 [2] 4 5");
+    let focused = ipc.workspaces().unwrap().into_iter().find(|w| w.is_focused).unwrap();
+    assert_eq!((focused.id, focused.name.as_deref()), (11, Some("2: web")));
+
+    drop(sway);
+    assert_eq!(ipc.workspace_summary().0, "WS ?");
+}
+
+#[test]
+fn windows_come_from_the_tree() {
+    let sway = FakeSway::start();
+    sway.set_tree(tree(vec![
+        (10, "1", vec![window(20, "vim", "foot", true)]),
+        (11, "2", vec![window(21, "Mozilla Firefox", "firefox", false)]),
+    ]));
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    let windows = ipc.windows().unwrap();
+    let ids: Vec<_> = windows.iter().map(|w| (w.id, w.workspace_id)).collect();
+    assert_eq!(ids, [(20, Some(10)), (21, Some(11))]);
+    assert_eq!(ipc.focused_window().unwrap().unwrap().title.as_deref(), Some("vim"));
+}
+
+#[test]
+fn actions_become_commands() {
+    let sway = FakeSway::start();
+    sway.set_workspaces(json!([workspace(11, 2, "2: \"web\"", true)]));
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    ipc.action(Action::FocusWorkspace { id: 11 }).unwrap();
+    ipc.action(Action::FocusWindow { id: 20 }).unwrap();
+    ipc.action(Action::CloseWindow { id: 20 }).unwrap();
+    ipc.action(Action::NextLayout).unwrap();
+    assert_eq!(
+        sway.messages(RUN_COMMAND),
+        [
+            r#"workspace "2: \"web\"""#,
+            "[con_id=20] focus",
+            "[con_id=20] kill",
+            "input type:keyboard xkb_switch_layout next",
+        ]
+    );
+
+    assert!(ipc.action(Action::FocusWorkspace { id: 99 }).is_err());
+}
+
+#[test]
+fn failed_commands_carry_the_sway_message() {
+    let sway = FakeSway::start();
+    sway.reply(RUN_COMMAND, json!([{"success": false, "error": "No matching node."}]));
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    let error = ipc.action(Action::FocusWindow { id: 5 }).unwrap_err();
+    assert_eq!(error.to_string(), "sway: No matching node.");
+}
+
+#[test]
+fn keyboard_layouts_come_from_the_first_keyboard() {
+    let sway = FakeSway::start();
+    sway.reply(GET_INPUTS, json!([
+        {"identifier": "1:1:Power_Button", "type": "switch"},
+        {"identifier": "1:1:AT_Keyboard", "type": "keyboard",
+         "xkb_layout_names": ["English (US)", "German"], "xkb_active_layout_index": 1},
+    ]));
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    let layouts = ipc.keyboard_layouts().unwrap();
+    assert_eq!(layouts.names[layouts.current_idx], "German");
+}
+
+#[test]
+fn taskbar_clicks_run_commands() {
+    let sway = FakeSway::start();
+    sway.set_workspaces(json!([workspace(10, 1, "1", true)]));
+    sway.set_tree(tree(vec![(
+        10,
+        "1",
+        vec![window(20, "vim", "foot", true), window(21, "Files", "org.gnome.Nautilus", false)],
+    )]));
+    let mut bar = bar_with_sway(
+        &sway,
+        r#"
+        modules_left = ["taskbar"]
+        modules_right = []
+
+        [style]
+        padding = 10
+        "#,
+    );
+    bar.render(400, 30);
+
+    // "[foo] nau"
+    assert!(bar.click(10.0 + 6.0 * 7.0, 15.0, Button::Left));
+    assert!(bar.click(10.0 + 6.0, 15.0, Button::Middle));
+    assert_eq!(sway.messages(RUN_COMMAND), ["[con_id=21] focus", "[con_id=20] kill"]);
+}
+
+#[test]
+fn event_stream_follows_subscriptions() {
+    let sway = FakeSway::start();
+    let ipc = SwayIpc::with_socket(sway.path());
+
+    let (sender, events) = mpsc::channel();
+    ipc.spawn_event_stream(Box::new(move || sender.send(()).is_ok()));
+    sway.wait_for_subscribers(1);
+    assert_eq!(sway.messages(SUBSCRIBE), [r#"["workspace","window","input"]"#]);
+
+    sway.emit(WORKSPACE_EVENT, json!({"change": "focus"}));
+    sway.emit(WINDOW_EVENT, json!({"change": "title"}));
+    for _ in 0..2 {
+        assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+    }
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+
+    // Losing the subscription prompts a redraw, then a new subscription
+    sway.drop_subscribers();
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+    sway.wait_for_subscribers(1);
+    sway.emit(WORKSPACE_EVENT, json!({"change": "init"}));
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_ok());
+}
+
+#[test]
+fn unresponsive_sway_times_out() {
+    let path = std::env::temp_dir().join(format!("oxidebar-sway-{}-silent.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    // Accepts connections but never answers
+    let _listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let ipc = SwayIpc::with_socket(&path);
+
+    let started = std::time::Instant::now();
+    assert!(ipc.workspaces().is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+    let _ = std::fs::remove_file(&path);
+}
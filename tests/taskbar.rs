@@ -0,0 +1,148 @@
+// Taskbar clicks against a fake niri
+
+mod support;
+
+use chrono::{Duration, Local};
+use oxidebar::bar::{BarRenderer, Button};
+use oxidebar::sources::{Clock, ManualClock};
+use serde_json::json;
+use support::{bar_with_niri, bar_with_niri_clock};
+use support::fake_niri::{window, workspace, FakeNiri};
+
+const CELL: f64 = 6.0;
+
+fn bar(niri: &FakeNiri) -> BarRenderer {
+    bar_with_niri(
+        niri,
+        r#"
+        modules_left = ["taskbar"]
+        modules_right = []
+
+        [style]
+        padding = 10
+        "#,
+    )
+}
+
+// Middle of the n-th character cell of the first left module
+fn cell(n: usize) -> f64 {
+    10.0 + CELL * n as f64 + CELL / 2.0
+}
+
+#[test]
+fn clicks_focus_and_close_windows() {
+    let niri = FakeNiri::start();
+    niri.set_workspaces(json!([workspace(1, 1, "DP-1", true), workspace(2, 2, "DP-1", false)]));
+    niri.set_windows(json!([
+        window(4, "Mozilla Firefox", "firefox", 1, true),
+        window(9, "Files", "org.gnome.Nautilus", 1, false),
+        window(5, "Terminal", "Alacritty", 2, false),
+    ]));
+
+    let mut bar = bar(&niri);
+    bar.render(400, 30);
+
+    // "[fir] nau"
+    assert!(bar.click(cell(7), 15.0, Button::Left));
+    assert!(bar.click(cell(1), 15.0, Button::Middle));
+    // The space between labels, a right click, and empty bar do nothing
+    assert!(!bar.click(cell(5), 15.0, Button::Left));
+    assert!(!bar.click(cell(7), 15.0, Button::Right));
+    assert!(!bar.click(300.0, 15.0, Button::Left));
+
+    let actions: Vec<String> = niri
+        .requests_after_handshake()
+        .into_iter()
+        .filter(|r| r.contains("Action"))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            r#"{"Action":{"FocusWindow":{"id":9}}}"#,
+            r#"{"Action":{"CloseWindow":{"id":4}}}"#,
+        ]
+    );
+}
+
+// "[fir] nau" squeezed into five cells
+fn overflowing(niri: &FakeNiri, overflow: &str, clock: &ManualClock) -> BarRenderer {
+    niri.set_workspaces(json!([workspace(1, 1, "DP-1", true)]));
+    niri.set_windows(json!([
+        window(4, "Mozilla Firefox", "firefox", 1, true),
+        window(9, "Files", "org.gnome.Nautilus", 1, false),
+    ]));
+    let toml = format!(
+        r#"
+        modules_left = ["taskbar"]
+        modules_right = []
+
+        [style]
+        padding = 10
+
+        [module_layout.taskbar]
+        max_width = 30
+        overflow = "{}"
+        marquee_speed = 48
+        "#,
+        overflow
+    );
+    bar_with_niri_clock(niri, &toml, clock)
+}
+
+fn actions(niri: &FakeNiri) -> Vec<String> {
+    niri.requests_after_handshake()
+        .into_iter()
+        .filter(|r| r.contains("Action"))
+        .collect()
+}
+
+#[test]
+fn clicks_past_an_ellipsis_do_nothing() {
+    let niri = FakeNiri::start();
+    let clock = ManualClock::new(Local::now());
+    let mut bar = overflowing(&niri, "ellipsis", &clock);
+    bar.render(400, 30);
+
+    // "[fir…": the ellipsis and the hidden label can't be clicked
+    assert!(bar.click(cell(1), 15.0, Button::Middle));
+    assert!(!bar.click(cell(4), 15.0, Button::Left));
+    assert!(!bar.click(cell(7), 15.0, Button::Left));
+
+    assert_eq!(actions(&niri), [r#"{"Action":{"CloseWindow":{"id":4}}}"#]);
+}
+
+#[test]
+fn clicks_follow_the_marquee() {
+    let niri = FakeNiri::start();
+    let clock = ManualClock::new(Local::now());
+    let mut bar = overflowing(&niri, "marquee", &clock);
+    bar.render(400, 30);
+
+    // Eight cells along, so the end of "nau" is under the first cell, the gap
+    // after it under the second and "[fir]" coming round again under the last
+    clock.set(clock.now() + Duration::seconds(1));
+    bar.render(400, 30);
+    assert!(bar.click(cell(0), 15.0, Button::Left));
+    assert!(!bar.click(cell(1), 15.0, Button::Left));
+    assert!(bar.click(cell(4), 15.0, Button::Middle));
+
+    assert_eq!(
+        actions(&niri),
+        [
+            r#"{"Action":{"FocusWindow":{"id":9}}}"#,
+            r#"{"Action":{"CloseWindow":{"id":4}}}"#,
+        ]
+    );
+}
+
+#[test]
+fn empty_workspaces_hide_the_taskbar() {
+    let niri = FakeNiri::start();
+    niri.set_workspaces(json!([workspace(1, 1, "DP-1", true)]));
+    niri.set_windows(json!([window(5, "Terminal", "Alacritty", 2, false)]));
+
+    let mut bar = bar(&niri);
+    bar.render(400, 30);
+
+    assert!(!bar.click(cell(0), 15.0, Button::Left));
+}